@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS tasks_user_id_date_idx;
+ALTER TABLE priorities
+    DROP COLUMN IF EXISTS rank;
\ No newline at end of file
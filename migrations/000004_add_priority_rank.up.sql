@@ -0,0 +1,13 @@
+ALTER TABLE priorities
+    ADD COLUMN rank INT NOT NULL DEFAULT 0;
+
+UPDATE priorities
+SET rank = CASE priority
+               WHEN 'Low' THEN 1
+               WHEN 'Medium' THEN 2
+               WHEN 'High' THEN 3
+               WHEN 'Vital' THEN 4
+    END
+WHERE priority IN ('Low', 'Medium', 'High', 'Vital');
+
+CREATE INDEX IF NOT EXISTS tasks_user_id_date_idx ON tasks (user_id, date, id);
\ No newline at end of file
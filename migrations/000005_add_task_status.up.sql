@@ -0,0 +1,4 @@
+ALTER TABLE tasks
+    ADD COLUMN status       VARCHAR(20) NOT NULL DEFAULT 'todo'
+        CHECK (status IN ('todo', 'in-progress', 'done', 'cancelled')),
+    ADD COLUMN completed_at TIMESTAMPTZ;
\ No newline at end of file
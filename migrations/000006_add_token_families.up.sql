@@ -0,0 +1,11 @@
+ALTER TABLE tokens
+    ADD COLUMN family_id UUID,
+    ADD COLUMN used      BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE tokens
+SET family_id = id;
+
+ALTER TABLE tokens
+    ALTER COLUMN family_id SET NOT NULL;
+
+CREATE INDEX IF NOT EXISTS tokens_family_id_idx ON tokens (family_id);
\ No newline at end of file
@@ -0,0 +1,7 @@
+ALTER TABLE tokens
+    ADD COLUMN created_at   TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    ADD COLUMN last_used_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    ADD COLUMN user_agent   TEXT,
+    ADD COLUMN ip           VARCHAR(45);
+
+CREATE INDEX IF NOT EXISTS tokens_user_id_idx ON tokens (user_id);
\ No newline at end of file
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS one_time_tokens
+(
+    hash       CHAR(64) PRIMARY KEY,
+    user_id    INT REFERENCES users (id) NOT NULL,
+    purpose    VARCHAR(32)               NOT NULL CHECK (purpose IN ('password-reset')),
+    expires_at TIMESTAMPTZ               NOT NULL,
+    used_at    TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS one_time_tokens_user_id_idx ON one_time_tokens (user_id);
\ No newline at end of file
@@ -0,0 +1,11 @@
+DELETE
+FROM one_time_tokens
+WHERE purpose = 'email-verification';
+
+ALTER TABLE one_time_tokens
+    DROP CONSTRAINT IF EXISTS one_time_tokens_purpose_check,
+    ADD CONSTRAINT one_time_tokens_purpose_check
+        CHECK (purpose IN ('password-reset'));
+
+ALTER TABLE users
+    DROP COLUMN IF EXISTS email_verified;
\ No newline at end of file
@@ -0,0 +1,10 @@
+ALTER TABLE users
+    ADD COLUMN email_verified BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE users
+SET email_verified = TRUE;
+
+ALTER TABLE one_time_tokens
+    DROP CONSTRAINT IF EXISTS one_time_tokens_purpose_check,
+    ADD CONSTRAINT one_time_tokens_purpose_check
+        CHECK (purpose IN ('password-reset', 'email-verification'));
\ No newline at end of file
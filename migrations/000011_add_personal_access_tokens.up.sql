@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS personal_access_tokens
+(
+    id           UUID PRIMARY KEY,
+    user_id      INT REFERENCES users (id) NOT NULL,
+    name         VARCHAR(100)              NOT NULL,
+    hash         CHAR(64) UNIQUE           NOT NULL,
+    scopes       TEXT[]                    NOT NULL,
+    created_at   TIMESTAMPTZ               NOT NULL DEFAULT NOW(),
+    expires_at   TIMESTAMPTZ,
+    last_used_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS personal_access_tokens_user_id_idx ON personal_access_tokens (user_id);
\ No newline at end of file
@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS login_failures
+(
+    key             VARCHAR(320) PRIMARY KEY,
+    failures        INT         NOT NULL,
+    last_failure_at TIMESTAMPTZ NOT NULL,
+    locked_until    TIMESTAMPTZ
+);
+
+CREATE TABLE IF NOT EXISTS lockout_events
+(
+    id           SERIAL PRIMARY KEY,
+    key          VARCHAR(320) NOT NULL,
+    failures     INT          NOT NULL,
+    locked_until TIMESTAMPTZ  NOT NULL,
+    created_at   TIMESTAMPTZ  NOT NULL DEFAULT NOW()
+);
\ No newline at end of file
@@ -0,0 +1,24 @@
+ALTER TABLE tokens
+    DROP CONSTRAINT IF EXISTS tokens_user_id_fkey,
+    ADD CONSTRAINT tokens_user_id_fkey
+        FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE;
+
+ALTER TABLE tasks
+    DROP CONSTRAINT IF EXISTS tasks_user_id_fkey,
+    ADD CONSTRAINT tasks_user_id_fkey
+        FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE;
+
+ALTER TABLE one_time_tokens
+    DROP CONSTRAINT IF EXISTS one_time_tokens_user_id_fkey,
+    ADD CONSTRAINT one_time_tokens_user_id_fkey
+        FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE;
+
+ALTER TABLE recovery_codes
+    DROP CONSTRAINT IF EXISTS recovery_codes_user_id_fkey,
+    ADD CONSTRAINT recovery_codes_user_id_fkey
+        FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE;
+
+ALTER TABLE personal_access_tokens
+    DROP CONSTRAINT IF EXISTS personal_access_tokens_user_id_fkey,
+    ADD CONSTRAINT personal_access_tokens_user_id_fkey
+        FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE;
\ No newline at end of file
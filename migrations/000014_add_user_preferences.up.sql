@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS user_preferences
+(
+    user_id          INT PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    timezone         VARCHAR(64)  NOT NULL DEFAULT 'UTC',
+    locale           VARCHAR(35)  NOT NULL DEFAULT 'en-US',
+    week_start       VARCHAR(10)  NOT NULL DEFAULT 'monday'
+        CHECK (week_start IN ('monday', 'sunday', 'saturday')),
+    default_priority VARCHAR(100) NOT NULL DEFAULT 'Medium' REFERENCES priorities (priority)
+);
\ No newline at end of file
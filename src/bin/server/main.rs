@@ -1,10 +1,13 @@
-use auth::Authenticator;
+use auth::{Authenticator, PemKey};
 use dotenvy::dotenv;
-use server::config::Config;
+use server::config::{Config, MailerConfig};
+use server::mail::{LogMailer, Mailer, SmtpMailer};
 use server::server::{Server, ServerConfig};
 use server::{auth, repositories, services, utils};
 use sqlx::PgPool;
+use std::path::PathBuf;
 use std::sync::Arc;
+use utils::breached_passwords::BreachedPasswords;
 use utils::clean_tokens::clean_tokens;
 
 #[tokio::main]
@@ -14,25 +17,84 @@ async fn main() {
     }
 
     let config = Config::new_from_env().expect("Failed to load config");
+    match config.mailer.clone() {
+        MailerConfig::Smtp { url } => {
+            let mailer =
+                SmtpMailer::new(&url, &config.mail_from).expect("Failed to create SMTP mailer");
+            run(config, mailer).await
+        }
+        MailerConfig::Log { dir } => run(config, LogMailer::new(dir.map(PathBuf::from))).await,
+    }
+}
+
+/// Function that will create and run the server sending mails with `mailer`.
+async fn run<M: Mailer>(mut config: Config, mailer: M) {
+    if let Some(path) = &config.breached_passwords_file {
+        let breached_passwords =
+            BreachedPasswords::from_file(path).expect("Failed to load breached passwords");
+        println!("Loaded {} breached passwords", breached_passwords.len());
+        config
+            .user_settings
+            .validation_policy
+            .password
+            .breached_passwords = Arc::new(breached_passwords);
+    }
+
     let database = PgPool::connect(&config.database_url)
         .await
         .expect("Failed to connect to database");
     clean_tokens(database.clone());
 
-    let authenticator = Arc::new(Authenticator::new(config.secret));
+    let token_settings = config.token_settings;
+    let authenticator = if config.jwt_keys.is_empty() {
+        Authenticator::new(config.secret.expect("Missing JWT secret"), token_settings)
+    } else {
+        let keys = config
+            .jwt_keys
+            .iter()
+            .map(|key| {
+                let pem = std::fs::read(&key.path).expect("Failed to read JWT key");
+                PemKey::new(key.kid.clone(), key.algorithm, pem)
+            })
+            .collect();
+        Authenticator::from_pem_keys(keys, token_settings).expect("Failed to load JWT keys")
+    };
+    let authenticator = Arc::new(authenticator);
 
     let user_repository = repositories::user::PostgresUserRepository::new(database.clone());
     let token_repository = repositories::token::PostgresTokenRepository::new(database.clone());
+    let one_time_token_repository =
+        repositories::one_time_token::PostgresOneTimeTokenRepository::new(database.clone());
+    let personal_access_token_repository =
+        repositories::personal_access_token::PostgresPersonalAccessTokenRepository::new(
+            database.clone(),
+        );
+    let login_attempt_repository =
+        repositories::login_attempt::PostgresLoginAttemptRepository::new(database.clone());
+    let preferences_repository =
+        Arc::new(repositories::preferences::PostgresPreferencesRepository::new(database.clone()));
     let user_service = services::user::DefaultUserService::new(
         Arc::new(user_repository),
         Arc::new(token_repository),
+        Arc::new(one_time_token_repository),
+        Arc::new(personal_access_token_repository),
+        Arc::new(login_attempt_repository),
+        preferences_repository.clone(),
+        mailer,
         authenticator.clone(),
-    );
+        config.user_settings,
+    )
+    .expect("Failed to create user service");
 
     let task_repository = repositories::task::PostgresTaskRepository::new(database.clone());
-    let task_service = services::task::DefaultTaskService::new(Arc::new(task_repository));
+    let task_service =
+        services::task::DefaultTaskService::new(Arc::new(task_repository), preferences_repository);
 
-    let server_config = ServerConfig::new(&config.server_addr, authenticator.clone());
+    let server_config = ServerConfig::new(
+        &config.server_addr,
+        authenticator.clone(),
+        config.rate_limits,
+    );
 
     let server = Server::new(server_config, user_service, task_service)
         .await
@@ -1,2 +1,3 @@
 pub mod user;
-pub mod task;
\ No newline at end of file
+pub mod task;
+pub mod auth;
\ No newline at end of file
@@ -0,0 +1,9 @@
+use crate::auth::PersonalAccessTokenVerifier;
+use crate::server::AuthState;
+use axum::extract::State;
+use axum::Json;
+use jsonwebtoken::jwk::JwkSet;
+
+pub async fn jwks<V: PersonalAccessTokenVerifier>(State(app): State<AuthState<V>>) -> Json<JwkSet> {
+    Json(app.authenticator.jwks().clone())
+}
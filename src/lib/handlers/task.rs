@@ -1,9 +1,9 @@
 use crate::auth;
-use crate::models::task::{Task, TaskPayload};
+use crate::models::task::{Task, TaskPage, TaskPayload, TaskQuery, TaskStatus, TaskStatusPayload};
 use crate::server::TaskState;
 use crate::services::task::TaskService;
 use crate::utils::api_error_response::APIResult;
-use axum::extract::{Path, State};
+use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
 use axum::{Extension, Json};
 use uuid::Uuid;
@@ -20,23 +20,75 @@ pub async fn add_task<T: TaskService>(
 pub async fn get_tasks<T: TaskService>(
     State(app): State<TaskState<T>>,
     Extension(claims): Extension<auth::AccessClaims>,
-) -> APIResult<Json<Vec<Task>>> {
-    let tasks = app.task_service.get_task(claims).await?;
-    Ok(Json(tasks))
+    Query(query): Query<TaskQuery>,
+) -> APIResult<Json<TaskPage>> {
+    if let Some(error) = query.validate() {
+        return Err(error);
+    }
+    let page = app.task_service.get_task(claims, &query).await?;
+    Ok(Json(page))
+}
+
+pub async fn get_task<T: TaskService>(
+    State(app): State<TaskState<T>>,
+    Extension(claims): Extension<auth::AccessClaims>,
+    Path(id): Path<Uuid>,
+) -> APIResult<Json<Task>> {
+    let task = app.task_service.get_task_by_id(id, claims).await?;
+    Ok(Json(task))
 }
 
 pub async fn update_task<T: TaskService>(
     State(app): State<TaskState<T>>,
+    Extension(claims): Extension<auth::AccessClaims>,
     Json(task): Json<Task>,
 ) -> APIResult<StatusCode> {
-    app.task_service.update_task(&task).await?;
+    app.task_service.update_task(&task, claims).await?;
     Ok(StatusCode::OK)
 }
 
+pub async fn change_task_status<T: TaskService>(
+    State(app): State<TaskState<T>>,
+    Extension(claims): Extension<auth::AccessClaims>,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<TaskStatusPayload>,
+) -> APIResult<Json<Task>> {
+    let task = app
+        .task_service
+        .change_task_status(id, payload.status, claims)
+        .await?;
+    Ok(Json(task))
+}
+
+pub async fn complete_task<T: TaskService>(
+    State(app): State<TaskState<T>>,
+    Extension(claims): Extension<auth::AccessClaims>,
+    Path(id): Path<Uuid>,
+) -> APIResult<Json<Task>> {
+    let task = app
+        .task_service
+        .change_task_status(id, TaskStatus::Done, claims)
+        .await?;
+    Ok(Json(task))
+}
+
+pub async fn reopen_task<T: TaskService>(
+    State(app): State<TaskState<T>>,
+    Extension(claims): Extension<auth::AccessClaims>,
+    Path(id): Path<Uuid>,
+) -> APIResult<Json<Task>> {
+    let task = app
+        .task_service
+        .change_task_status(id, TaskStatus::Todo, claims)
+        .await?;
+    Ok(Json(task))
+}
+
 pub async fn delete_task<T: TaskService>(
     State(app): State<TaskState<T>>,
+    Extension(claims): Extension<auth::AccessClaims>,
     Path(id): Path<Uuid>,
 ) -> APIResult<StatusCode> {
-    app.task_service.delete_task(id).await?;
+    app.task_service.delete_task(id, claims).await?;
     Ok(StatusCode::OK)
 }
@@ -1,35 +1,303 @@
 use crate::auth;
+use crate::models::one_time_token::TokenQuery;
+use crate::models::personal_access_token::{
+    CreatedPersonalAccessToken, PersonalAccessToken, PersonalAccessTokenPayload,
+};
+use crate::models::policy::ValidationPolicy;
+use crate::models::preferences::{Preferences, PreferencesPayload};
+use crate::models::session::{ClientInfo, Session};
 use crate::models::token_group::TokenGroup;
-use crate::models::user::UserPayload;
-use crate::server::UserState;
+use crate::models::two_factor::{
+    LoginResponse, PasswordPayload, RecoveryCodes, TwoFactorCodePayload, TwoFactorLoginPayload,
+    TwoFactorSetup,
+};
+use crate::models::user::{
+    EmailPayload, LoginPayload, PasswordChangePayload, PasswordResetPayload, User, UserDataExport,
+    UserPayload, UserUpdatePayload,
+};
+use crate::server::{TaskState, UserState};
+use crate::services::task::TaskService;
 use crate::services::user::UserService;
 use crate::utils::api_error_response::APIResult;
-use axum::extract::State;
-use axum::http::StatusCode;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
 use axum::{Extension, Json};
+use uuid::Uuid;
 
 pub async fn register<T: UserService>(
     State(app): State<UserState<T>>,
     Json(mut user): Json<UserPayload>,
 ) -> APIResult<StatusCode> {
-    if let Some(error) = user.validate() {
+    if let Some(error) = user.validate(app.user_service.validation_policy()) {
         return Err(error);
     }
     app.user_service.register(&mut user).await
 }
 
+pub async fn get_validation_policy<T: UserService>(
+    State(app): State<UserState<T>>,
+) -> Json<ValidationPolicy> {
+    Json(app.user_service.validation_policy().clone())
+}
+
 pub async fn login<T: UserService>(
     State(app): State<UserState<T>>,
-    Json(user): Json<UserPayload>,
+    client: ClientInfo,
+    Json(payload): Json<LoginPayload>,
+) -> APIResult<Json<LoginResponse>> {
+    let response = app.user_service.login(&payload, &client).await?;
+    Ok(Json(response))
+}
+
+pub async fn login_two_factor<T: UserService>(
+    State(app): State<UserState<T>>,
+    client: ClientInfo,
+    Json(payload): Json<TwoFactorLoginPayload>,
 ) -> APIResult<Json<TokenGroup>> {
-    let group = app.user_service.login(&user).await?;
+    let group = app.user_service.login_two_factor(&payload, &client).await?;
     Ok(Json(group))
 }
 
 pub async fn refresh<T: UserService>(
     State(app): State<UserState<T>>,
     Extension(claims): Extension<auth::RefreshClaims>,
+    client: ClientInfo,
+) -> APIResult<Json<TokenGroup>> {
+    let group = app.user_service.refresh(claims, &client).await?;
+    Ok(Json(group))
+}
+
+pub async fn logout<T: UserService>(
+    State(app): State<UserState<T>>,
+    Extension(claims): Extension<auth::RefreshClaims>,
+) -> APIResult<StatusCode> {
+    app.user_service.logout(claims).await?;
+    Ok(StatusCode::OK)
+}
+
+pub async fn logout_all<T: UserService>(
+    State(app): State<UserState<T>>,
+    Extension(claims): Extension<auth::AccessClaims>,
+) -> APIResult<StatusCode> {
+    app.user_service.logout_all(claims).await?;
+    Ok(StatusCode::OK)
+}
+
+pub async fn get_current_user<T: UserService>(
+    State(app): State<UserState<T>>,
+    Extension(claims): Extension<auth::AccessClaims>,
+) -> APIResult<Json<User>> {
+    let user = app.user_service.get_current_user(claims).await?;
+    Ok(Json(user))
+}
+
+pub async fn update_current_user<T: UserService>(
+    State(app): State<UserState<T>>,
+    Extension(claims): Extension<auth::AccessClaims>,
+    Json(payload): Json<UserUpdatePayload>,
+) -> APIResult<Json<User>> {
+    if let Some(error) = payload.validate(app.user_service.validation_policy()) {
+        return Err(error);
+    }
+
+    let user = app
+        .user_service
+        .update_current_user(&payload, claims)
+        .await?;
+    Ok(Json(user))
+}
+
+pub async fn delete_current_user<T: UserService>(
+    State(app): State<UserState<T>>,
+    Extension(claims): Extension<auth::AccessClaims>,
+    Json(payload): Json<PasswordPayload>,
+) -> APIResult<StatusCode> {
+    app.user_service
+        .delete_current_user(&payload.password, claims)
+        .await?;
+    Ok(StatusCode::OK)
+}
+
+pub async fn export_current_user<U: UserService, T: TaskService>(
+    State(app): State<UserState<U>>,
+    State(task_app): State<TaskState<T>>,
+    Extension(claims): Extension<auth::AccessClaims>,
+) -> APIResult<impl IntoResponse> {
+    let export = UserDataExport::new(
+        app.user_service.get_current_user(claims.clone()).await?,
+        app.user_service.get_preferences(claims.clone()).await?,
+        app.user_service.get_sessions(claims.clone()).await?,
+        app.user_service
+            .get_personal_access_tokens(claims.clone())
+            .await?,
+        task_app.task_service.get_all_tasks(claims).await?,
+    );
+
+    Ok((
+        [(
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"user-data.json\"",
+        )],
+        Json(export),
+    ))
+}
+
+pub async fn get_preferences<T: UserService>(
+    State(app): State<UserState<T>>,
+    Extension(claims): Extension<auth::AccessClaims>,
+) -> APIResult<Json<Preferences>> {
+    let preferences = app.user_service.get_preferences(claims).await?;
+    Ok(Json(preferences))
+}
+
+pub async fn update_preferences<T: UserService>(
+    State(app): State<UserState<T>>,
+    Extension(claims): Extension<auth::AccessClaims>,
+    Json(payload): Json<PreferencesPayload>,
+) -> APIResult<Json<Preferences>> {
+    if let Some(error) = payload.validate() {
+        return Err(error);
+    }
+
+    let preferences = app
+        .user_service
+        .update_preferences(&payload, claims)
+        .await?;
+    Ok(Json(preferences))
+}
+
+pub async fn get_sessions<T: UserService>(
+    State(app): State<UserState<T>>,
+    Extension(claims): Extension<auth::AccessClaims>,
+) -> APIResult<Json<Vec<Session>>> {
+    let sessions = app.user_service.get_sessions(claims).await?;
+    Ok(Json(sessions))
+}
+
+pub async fn revoke_session<T: UserService>(
+    State(app): State<UserState<T>>,
+    Extension(claims): Extension<auth::AccessClaims>,
+    Path(id): Path<Uuid>,
+) -> APIResult<StatusCode> {
+    app.user_service.revoke_session(id, claims).await?;
+    Ok(StatusCode::OK)
+}
+
+pub async fn change_password<T: UserService>(
+    State(app): State<UserState<T>>,
+    Extension(claims): Extension<auth::AccessClaims>,
+    client: ClientInfo,
+    Json(payload): Json<PasswordChangePayload>,
 ) -> APIResult<Json<TokenGroup>> {
-    let group = app.user_service.refresh(claims).await?;
+    if let Some(error) = payload.validate(app.user_service.validation_policy()) {
+        return Err(error);
+    }
+    let group = app
+        .user_service
+        .change_password(&payload, claims, &client)
+        .await?;
     Ok(Json(group))
 }
+
+pub async fn forgot_password<T: UserService>(
+    State(app): State<UserState<T>>,
+    Json(payload): Json<EmailPayload>,
+) -> APIResult<StatusCode> {
+    app.user_service
+        .request_password_reset(&payload.email)
+        .await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+pub async fn reset_password<T: UserService>(
+    State(app): State<UserState<T>>,
+    Json(payload): Json<PasswordResetPayload>,
+) -> APIResult<StatusCode> {
+    if let Some(error) = payload.validate(app.user_service.validation_policy()) {
+        return Err(error);
+    }
+    app.user_service.reset_password(&payload).await?;
+    Ok(StatusCode::OK)
+}
+
+pub async fn verify_email<T: UserService>(
+    State(app): State<UserState<T>>,
+    Query(query): Query<TokenQuery>,
+) -> APIResult<StatusCode> {
+    app.user_service.verify_email(&query.token).await?;
+    Ok(StatusCode::OK)
+}
+
+pub async fn resend_verification<T: UserService>(
+    State(app): State<UserState<T>>,
+    Json(payload): Json<EmailPayload>,
+) -> APIResult<StatusCode> {
+    app.user_service.resend_verification(&payload.email).await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+pub async fn setup_two_factor<T: UserService>(
+    State(app): State<UserState<T>>,
+    Extension(claims): Extension<auth::AccessClaims>,
+) -> APIResult<Json<TwoFactorSetup>> {
+    let setup = app.user_service.setup_two_factor(claims).await?;
+    Ok(Json(setup))
+}
+
+pub async fn enable_two_factor<T: UserService>(
+    State(app): State<UserState<T>>,
+    Extension(claims): Extension<auth::AccessClaims>,
+    Json(payload): Json<TwoFactorCodePayload>,
+) -> APIResult<Json<RecoveryCodes>> {
+    let codes = app
+        .user_service
+        .enable_two_factor(&payload.code, claims)
+        .await?;
+    Ok(Json(codes))
+}
+
+pub async fn disable_two_factor<T: UserService>(
+    State(app): State<UserState<T>>,
+    Extension(claims): Extension<auth::AccessClaims>,
+    Json(payload): Json<PasswordPayload>,
+) -> APIResult<StatusCode> {
+    app.user_service
+        .disable_two_factor(&payload.password, claims)
+        .await?;
+    Ok(StatusCode::OK)
+}
+
+pub async fn create_personal_access_token<T: UserService>(
+    State(app): State<UserState<T>>,
+    Extension(claims): Extension<auth::AccessClaims>,
+    Json(payload): Json<PersonalAccessTokenPayload>,
+) -> APIResult<(StatusCode, Json<CreatedPersonalAccessToken>)> {
+    if let Some(error) = payload.validate() {
+        return Err(error);
+    }
+    let token = app
+        .user_service
+        .create_personal_access_token(&payload, claims)
+        .await?;
+    Ok((StatusCode::CREATED, Json(token)))
+}
+
+pub async fn get_personal_access_tokens<T: UserService>(
+    State(app): State<UserState<T>>,
+    Extension(claims): Extension<auth::AccessClaims>,
+) -> APIResult<Json<Vec<PersonalAccessToken>>> {
+    let tokens = app.user_service.get_personal_access_tokens(claims).await?;
+    Ok(Json(tokens))
+}
+
+pub async fn revoke_personal_access_token<T: UserService>(
+    State(app): State<UserState<T>>,
+    Extension(claims): Extension<auth::AccessClaims>,
+    Path(id): Path<Uuid>,
+) -> APIResult<StatusCode> {
+    app.user_service
+        .revoke_personal_access_token(id, claims)
+        .await?;
+    Ok(StatusCode::OK)
+}
@@ -5,4 +5,6 @@ pub mod repositories;
 pub mod services;
 pub mod utils;
 pub mod handlers;
-pub mod auth;
\ No newline at end of file
+pub mod auth;
+pub mod mail;
+pub mod rate_limit;
\ No newline at end of file
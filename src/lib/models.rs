@@ -1,3 +1,9 @@
 pub mod user;
 pub mod token_group;
-pub mod task;
\ No newline at end of file
+pub mod task;
+pub mod session;
+pub mod one_time_token;
+pub mod two_factor;
+pub mod personal_access_token;
+pub mod preferences;
+pub mod policy;
\ No newline at end of file
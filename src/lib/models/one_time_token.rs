@@ -0,0 +1,70 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+/// Purpose of a one-time token, a token can only be used for its purpose.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenPurpose {
+    PasswordReset,
+    EmailVerification,
+}
+
+impl TokenPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenPurpose::PasswordReset => "password-reset",
+            TokenPurpose::EmailVerification => "email-verification",
+        }
+    }
+}
+
+/// Single use token sent to the user by mail.
+/// Only the hash of the token is stored, so a leaked database can't be used to take over accounts.
+pub struct OneTimeToken {
+    /// The token sent to the user.
+    pub token: String,
+    /// SHA-256 hash of the token in hex.
+    pub hash: String,
+}
+
+impl OneTimeToken {
+    /// Method that will generate new random token.
+    pub fn generate() -> Self {
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let token = URL_SAFE_NO_PAD.encode(bytes);
+        let hash = Self::hash(&token);
+        Self { token, hash }
+    }
+
+    /// Method that will hash the token presented by the user.
+    /// The tokens have enough entropy that a fast hash is sufficient.
+    pub fn hash(token: &str) -> String {
+        hex::encode(Sha256::digest(token.as_bytes()))
+    }
+}
+
+/// `TokenQuery` holds one-time token sent as query parameter of a link.
+#[derive(Deserialize)]
+pub struct TokenQuery {
+    pub token: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate() {
+        let first = OneTimeToken::generate();
+        let second = OneTimeToken::generate();
+        assert_ne!(first.token, second.token);
+        assert_eq!(first.token.len(), 43);
+        assert_eq!(first.hash.len(), 64);
+        assert_eq!(OneTimeToken::hash(&first.token), first.hash);
+        assert_ne!(OneTimeToken::hash(&second.token), first.hash);
+    }
+}
@@ -0,0 +1,149 @@
+use crate::auth::Scope;
+use crate::models::one_time_token::OneTimeToken;
+use crate::utils::api_error_response::APIErrorResponse;
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Prefix of the personal access tokens, used to tell them apart from JWTs.
+pub const PERSONAL_ACCESS_TOKEN_PREFIX: &str = "tsk_";
+
+/// Maximum length of the name of a personal access token.
+const MAX_NAME_LENGTH: usize = 100;
+
+/// Struct holding a long-lived token created by the user for scripts and integrations.
+/// Only the hash of the token is stored, the token itself is shown once when created.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PersonalAccessToken {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub user_id: i32,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: DateTime<Utc>,
+    /// Time after which the token is rejected, `None` if it never expires.
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl PersonalAccessToken {
+    pub fn new(
+        id: Uuid,
+        user_id: i32,
+        name: String,
+        scopes: Vec<Scope>,
+        created_at: DateTime<Utc>,
+        expires_at: Option<DateTime<Utc>>,
+        last_used_at: Option<DateTime<Utc>>,
+    ) -> Self {
+        Self {
+            id,
+            user_id,
+            name,
+            scopes,
+            created_at,
+            expires_at,
+            last_used_at,
+        }
+    }
+
+    /// Method that will generate new random token with `PERSONAL_ACCESS_TOKEN_PREFIX`.
+    pub fn generate() -> OneTimeToken {
+        let token = format!(
+            "{}{}",
+            PERSONAL_ACCESS_TOKEN_PREFIX,
+            OneTimeToken::generate().token
+        );
+        let hash = OneTimeToken::hash(&token);
+        OneTimeToken { token, hash }
+    }
+}
+
+/// `PersonalAccessTokenPayload` holds the data used to create personal access token.
+#[derive(Deserialize)]
+pub struct PersonalAccessTokenPayload {
+    pub name: String,
+    /// Scopes granted to the token, all scopes when not set.
+    pub scopes: Option<Vec<Scope>>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl PersonalAccessTokenPayload {
+    pub fn validate(&self) -> Option<APIErrorResponse> {
+        let name = self.name.trim();
+        if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+            return Some(APIErrorResponse::new(
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Token name should be between 1 and {} characters",
+                    MAX_NAME_LENGTH
+                ),
+            ));
+        }
+
+        if self.scopes.as_ref().is_some_and(|scopes| scopes.is_empty()) {
+            return Some(APIErrorResponse::new(
+                StatusCode::BAD_REQUEST,
+                String::from("Token should have at least one scope"),
+            ));
+        }
+
+        if self
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+        {
+            return Some(APIErrorResponse::new(
+                StatusCode::BAD_REQUEST,
+                String::from("Token expiry should be in the future"),
+            ));
+        }
+
+        None
+    }
+}
+
+/// Struct send by the server when personal access token is created.
+/// The token is shown only once.
+#[derive(Serialize, Debug)]
+pub struct CreatedPersonalAccessToken {
+    pub token: String,
+    #[serde(flatten)]
+    pub personal_access_token: PersonalAccessToken,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_generate() {
+        let token = PersonalAccessToken::generate();
+        assert!(token.token.starts_with(PERSONAL_ACCESS_TOKEN_PREFIX));
+        assert_eq!(OneTimeToken::hash(&token.token), token.hash);
+    }
+
+    #[test]
+    fn test_validate() {
+        let payload =
+            |name: &str, scopes: Option<Vec<Scope>>, expires_at: Option<DateTime<Utc>>| {
+                PersonalAccessTokenPayload {
+                    name: String::from(name),
+                    scopes,
+                    expires_at,
+                }
+            };
+        let tomorrow = Utc::now() + Duration::days(1);
+        let yesterday = Utc::now() - Duration::days(1);
+
+        assert!(payload("ci", None, None).validate().is_none());
+        assert!(payload("ci", Some(vec![Scope::TasksRead]), Some(tomorrow))
+            .validate()
+            .is_none());
+        assert!(payload(" ", None, None).validate().is_some());
+        assert!(payload(&"a".repeat(101), None, None).validate().is_some());
+        assert!(payload("ci", Some(vec![]), None).validate().is_some());
+        assert!(payload("ci", None, Some(yesterday)).validate().is_some());
+    }
+}
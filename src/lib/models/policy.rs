@@ -0,0 +1,293 @@
+use crate::utils::api_error_response::APIErrorResponse;
+use crate::utils::breached_passwords::BreachedPasswords;
+use axum::http::StatusCode;
+use serde::Serialize;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Longest username that fits in the database.
+pub const MAX_USERNAME_LENGTH: usize = 255;
+
+/// Longest password that can be configured.
+pub const MAX_PASSWORD_LENGTH: usize = 1024;
+
+/// Characters allowed in usernames. `@` is never allowed, so usernames can't be mistaken for emails.
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum UsernameCharset {
+    /// Any characters.
+    #[default]
+    Any,
+    /// ASCII letters, digits, `_`, `-` and `.`.
+    Alphanumeric,
+}
+
+impl FromStr for UsernameCharset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "any" => Ok(UsernameCharset::Any),
+            "alphanumeric" => Ok(UsernameCharset::Alphanumeric),
+            _ => Err(format!("Invalid username charset {}", s)),
+        }
+    }
+}
+
+/// Class of characters that can be required in passwords.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CharacterClass {
+    Digit,
+    Uppercase,
+    Lowercase,
+    /// ASCII punctuation like `!`, `_` or `~`.
+    Special,
+}
+
+impl CharacterClass {
+    /// Method used to check whether `c` belongs to the class.
+    pub fn contains(&self, c: char) -> bool {
+        match self {
+            CharacterClass::Digit => c.is_numeric(),
+            CharacterClass::Uppercase => c.is_uppercase(),
+            CharacterClass::Lowercase => c.is_lowercase(),
+            CharacterClass::Special => c.is_ascii_punctuation(),
+        }
+    }
+
+    /// Method returning the name of the class used in error messages.
+    fn description(&self) -> &'static str {
+        match self {
+            CharacterClass::Digit => "number",
+            CharacterClass::Uppercase => "uppercase letter",
+            CharacterClass::Lowercase => "lowercase letter",
+            CharacterClass::Special => "special character",
+        }
+    }
+}
+
+impl FromStr for CharacterClass {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "digit" => Ok(CharacterClass::Digit),
+            "uppercase" => Ok(CharacterClass::Uppercase),
+            "lowercase" => Ok(CharacterClass::Lowercase),
+            "special" => Ok(CharacterClass::Special),
+            _ => Err(format!("Invalid character class {}", s)),
+        }
+    }
+}
+
+/// Rules of the usernames, the lengths are in characters.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct UsernamePolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub charset: UsernameCharset,
+}
+
+impl Default for UsernamePolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            max_length: MAX_USERNAME_LENGTH,
+            charset: UsernameCharset::Any,
+        }
+    }
+}
+
+impl UsernamePolicy {
+    pub fn validate(&self, username: &str) -> Option<APIErrorResponse> {
+        let error = |message: String| Some(APIErrorResponse::new(StatusCode::BAD_REQUEST, message));
+
+        let length = username.chars().count();
+        if length < self.min_length {
+            return error(format!(
+                "Username should be more than {} characters",
+                self.min_length.saturating_sub(1)
+            ));
+        }
+
+        if length > self.max_length {
+            return error(format!(
+                "Username should be at most {} characters",
+                self.max_length
+            ));
+        }
+
+        // Logins containing @ are looked up by email.
+        if username.contains('@') {
+            return error(String::from("Username shouldn't contain @"));
+        }
+
+        if self.charset == UsernameCharset::Alphanumeric
+            && !username
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        {
+            return error(String::from(
+                "Username can only contain letters, digits, _, - and .",
+            ));
+        }
+
+        None
+    }
+}
+
+/// Rules of the passwords, the lengths are in characters.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    /// Classes of which the password has to contain at least one character.
+    pub required_classes: Vec<CharacterClass>,
+    /// Breached or common passwords that are rejected, loaded at startup.
+    #[serde(skip)]
+    pub breached_passwords: Arc<BreachedPasswords>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            max_length: 128,
+            required_classes: vec![
+                CharacterClass::Digit,
+                CharacterClass::Uppercase,
+                CharacterClass::Lowercase,
+                CharacterClass::Special,
+            ],
+            breached_passwords: Arc::default(),
+        }
+    }
+}
+
+impl PasswordPolicy {
+    pub fn validate(&self, password: &str) -> Option<APIErrorResponse> {
+        let error = |message: String| Some(APIErrorResponse::new(StatusCode::BAD_REQUEST, message));
+
+        let length = password.chars().count();
+        if length < self.min_length {
+            return error(String::from("Password is too short"));
+        }
+
+        if length > self.max_length {
+            return error(String::from("Password is too long"));
+        }
+
+        for class in &self.required_classes {
+            if !password.chars().any(|c| class.contains(c)) {
+                return error(format!(
+                    "Password must contain at least one {}",
+                    class.description()
+                ));
+            }
+        }
+
+        if self.breached_passwords.contains(password) {
+            return error(String::from(
+                "Password is too common or was found in a data breach",
+            ));
+        }
+
+        None
+    }
+}
+
+/// `ValidationPolicy` holds the rules of the usernames and passwords of the users,
+/// exposed to clients so they can validate before sending.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ValidationPolicy {
+    pub username: UsernamePolicy,
+    pub password: PasswordPolicy,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bad_request(message: &str) -> Option<APIErrorResponse> {
+        Some(APIErrorResponse::new(
+            StatusCode::BAD_REQUEST,
+            String::from(message),
+        ))
+    }
+
+    #[test]
+    fn test_username_policy() {
+        let policy = UsernamePolicy {
+            min_length: 3,
+            max_length: 10,
+            charset: UsernameCharset::Alphanumeric,
+        };
+
+        assert_eq!(policy.validate("jo.doe-1_"), None);
+        assert_eq!(
+            policy.validate("jo"),
+            bad_request("Username should be more than 2 characters")
+        );
+        assert_eq!(
+            policy.validate("jonathan_doe"),
+            bad_request("Username should be at most 10 characters")
+        );
+        assert_eq!(
+            policy.validate("jo doe"),
+            bad_request("Username can only contain letters, digits, _, - and .")
+        );
+        assert_eq!(
+            policy.validate("jo@doe"),
+            bad_request("Username shouldn't contain @")
+        );
+
+        // Lengths are counted in characters, not bytes.
+        let policy = UsernamePolicy::default();
+        assert_eq!(
+            policy.validate("Ünïcödé"),
+            bad_request("Username should be more than 7 characters")
+        );
+        assert_eq!(policy.validate("Ünïcödé_"), None);
+    }
+
+    #[test]
+    fn test_password_policy() {
+        let policy = PasswordPolicy {
+            min_length: 12,
+            max_length: 16,
+            required_classes: vec![CharacterClass::Digit],
+            breached_passwords: Arc::new(BreachedPasswords::new(["Password_123"])),
+        };
+
+        assert_eq!(policy.validate("correcthorse1"), None);
+        assert_eq!(
+            policy.validate("horse1"),
+            bad_request("Password is too short")
+        );
+        assert_eq!(
+            policy.validate("correcthorsebattery1"),
+            bad_request("Password is too long")
+        );
+        assert_eq!(
+            policy.validate("correcthorsebat"),
+            bad_request("Password must contain at least one number")
+        );
+
+        let policy = PasswordPolicy {
+            required_classes: Vec::new(),
+            ..policy
+        };
+        assert_eq!(policy.validate("correcthorsebat"), None);
+
+        assert_eq!(PasswordPolicy::default().validate("Password_123"), None);
+        assert_eq!(
+            PasswordPolicy {
+                min_length: 8,
+                ..policy
+            }
+            .validate("password_123"),
+            bad_request("Password is too common or was found in a data breach")
+        );
+    }
+}
@@ -0,0 +1,312 @@
+use crate::utils::api_error_response::APIErrorResponse;
+use axum::http::StatusCode;
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Maximum length of a locale tag.
+pub const MAX_LOCALE_LENGTH: usize = 35;
+
+/// Day the week starts on in the calendar of the user.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WeekStart {
+    #[default]
+    Monday,
+    Sunday,
+    Saturday,
+}
+
+impl WeekStart {
+    /// Method returning the name of the day as stored in the database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WeekStart::Monday => "monday",
+            WeekStart::Sunday => "sunday",
+            WeekStart::Saturday => "saturday",
+        }
+    }
+}
+
+impl fmt::Display for WeekStart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for WeekStart {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "monday" => Ok(WeekStart::Monday),
+            "sunday" => Ok(WeekStart::Sunday),
+            "saturday" => Ok(WeekStart::Saturday),
+            _ => Err(format!("Invalid week start {}", s)),
+        }
+    }
+}
+
+/// `Preferences` holds the settings of the user.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Preferences {
+    /// IANA name of the time zone used to find the days of the tasks, like `Europe/Berlin`.
+    pub timezone: String,
+    /// BCP 47 language tag, like `en-US`.
+    pub locale: String,
+    pub week_start: WeekStart,
+    /// Priority of the tasks added without priority.
+    pub default_priority: String,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self {
+            timezone: String::from("UTC"),
+            locale: String::from("en-US"),
+            week_start: WeekStart::default(),
+            default_priority: String::from("Medium"),
+        }
+    }
+}
+
+impl Preferences {
+    pub fn new(
+        timezone: String,
+        locale: String,
+        week_start: WeekStart,
+        default_priority: String,
+    ) -> Self {
+        Self {
+            timezone,
+            locale,
+            week_start,
+            default_priority,
+        }
+    }
+
+    /// Method returning the time zone of the user, UTC if the stored one is unknown.
+    pub fn tz(&self) -> Tz {
+        self.timezone.parse().unwrap_or(Tz::UTC)
+    }
+
+    /// Method returning the first and the last instant of `day` in the time zone of the user.
+    pub fn day_bounds(&self, day: NaiveDate) -> (DateTime<Utc>, DateTime<Utc>) {
+        let tz = self.tz();
+        let start = |day: NaiveDate| {
+            let midnight = day.and_hms_opt(0, 0, 0).unwrap_or_default();
+            // Days starting in a DST gap begin at the end of the gap,
+            // found in 15 minute steps since the gaps start and end on quarter hours.
+            (0..=24 * 4)
+                .find_map(|step| {
+                    tz.from_local_datetime(&(midnight + Duration::minutes(15 * step)))
+                        .earliest()
+                })
+                .unwrap_or_else(|| tz.from_utc_datetime(&midnight))
+                .with_timezone(&Utc)
+        };
+
+        let next_day = day.succ_opt().unwrap_or(day);
+        (start(day), start(next_day) - Duration::microseconds(1))
+    }
+
+    /// Method returning the current day in the time zone of the user.
+    pub fn today(&self, now: DateTime<Utc>) -> NaiveDate {
+        now.with_timezone(&self.tz()).date_naive()
+    }
+}
+
+/// `PreferencesPayload` holds the changes of the preferences, `None` fields are kept.
+#[derive(Deserialize, Default)]
+pub struct PreferencesPayload {
+    pub timezone: Option<String>,
+    pub locale: Option<String>,
+    pub week_start: Option<WeekStart>,
+    pub default_priority: Option<String>,
+}
+
+impl PreferencesPayload {
+    pub fn validate(&self) -> Option<APIErrorResponse> {
+        if let Some(timezone) = &self.timezone {
+            if timezone.parse::<Tz>().is_err() {
+                return Some(APIErrorResponse::new(
+                    StatusCode::BAD_REQUEST,
+                    format!("Unknown timezone {}", timezone),
+                ));
+            }
+        }
+
+        if let Some(locale) = &self.locale {
+            if !is_valid_locale(locale) {
+                return Some(APIErrorResponse::new(
+                    StatusCode::BAD_REQUEST,
+                    String::from("Locale should be a language tag like en-US"),
+                ));
+            }
+        }
+
+        if let Some(priority) = &self.default_priority {
+            if priority.is_empty() {
+                return Some(APIErrorResponse::new(
+                    StatusCode::BAD_REQUEST,
+                    String::from("Priority cannot be empty"),
+                ));
+            }
+        }
+
+        None
+    }
+
+    /// Method that will return `preferences` with the changes applied.
+    pub fn apply(&self, preferences: Preferences) -> Preferences {
+        Preferences {
+            timezone: self.timezone.clone().unwrap_or(preferences.timezone),
+            locale: self.locale.clone().unwrap_or(preferences.locale),
+            week_start: self.week_start.unwrap_or(preferences.week_start),
+            default_priority: self
+                .default_priority
+                .clone()
+                .unwrap_or(preferences.default_priority),
+        }
+    }
+}
+
+/// Function that will check the shape of the language tag,
+/// a language of 2 or 3 letters followed by subtags of 1 to 8 letters or digits.
+fn is_valid_locale(locale: &str) -> bool {
+    if locale.len() > MAX_LOCALE_LENGTH {
+        return false;
+    }
+
+    let mut subtags = locale.split('-');
+    let language = subtags.next().unwrap_or_default();
+    (2..=3).contains(&language.len())
+        && language.chars().all(|c| c.is_ascii_alphabetic())
+        && subtags.all(|subtag| {
+            (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let tests = vec![
+            (PreferencesPayload::default(), None),
+            (
+                PreferencesPayload {
+                    timezone: Some(String::from("Europe/Berlin")),
+                    locale: Some(String::from("de-DE")),
+                    week_start: Some(WeekStart::Sunday),
+                    default_priority: Some(String::from("High")),
+                },
+                None,
+            ),
+            (
+                PreferencesPayload {
+                    timezone: Some(String::from("Mars/Olympus")),
+                    ..Default::default()
+                },
+                Some(APIErrorResponse::new(
+                    StatusCode::BAD_REQUEST,
+                    String::from("Unknown timezone Mars/Olympus"),
+                )),
+            ),
+            (
+                PreferencesPayload {
+                    locale: Some(String::from("english")),
+                    ..Default::default()
+                },
+                Some(APIErrorResponse::new(
+                    StatusCode::BAD_REQUEST,
+                    String::from("Locale should be a language tag like en-US"),
+                )),
+            ),
+            (
+                PreferencesPayload {
+                    locale: Some(String::from("en-")),
+                    ..Default::default()
+                },
+                Some(APIErrorResponse::new(
+                    StatusCode::BAD_REQUEST,
+                    String::from("Locale should be a language tag like en-US"),
+                )),
+            ),
+            (
+                PreferencesPayload {
+                    default_priority: Some(String::new()),
+                    ..Default::default()
+                },
+                Some(APIErrorResponse::new(
+                    StatusCode::BAD_REQUEST,
+                    String::from("Priority cannot be empty"),
+                )),
+            ),
+        ];
+
+        for (payload, expected) in tests {
+            assert_eq!(expected, payload.validate());
+        }
+    }
+
+    #[test]
+    fn test_day_bounds() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let utc = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        let preferences = Preferences {
+            timezone: String::from("Europe/Berlin"),
+            ..Preferences::default()
+        };
+
+        assert_eq!(
+            preferences.day_bounds(date(2025, 1, 15)),
+            (
+                utc("2025-01-14T23:00:00Z"),
+                utc("2025-01-15T22:59:59.999999Z")
+            )
+        );
+        // The day DST starts has 23 hours.
+        assert_eq!(
+            preferences.day_bounds(date(2025, 3, 30)),
+            (
+                utc("2025-03-29T23:00:00Z"),
+                utc("2025-03-30T21:59:59.999999Z")
+            )
+        );
+
+        // DST starts at midnight in Santiago, so the day begins at 01:00.
+        let preferences = Preferences {
+            timezone: String::from("America/Santiago"),
+            ..Preferences::default()
+        };
+        assert_eq!(
+            preferences.day_bounds(date(2025, 9, 7)),
+            (
+                utc("2025-09-07T04:00:00Z"),
+                utc("2025-09-08T02:59:59.999999Z")
+            )
+        );
+        assert_eq!(
+            preferences.day_bounds(date(2025, 9, 6)).1,
+            utc("2025-09-07T03:59:59.999999Z")
+        );
+
+        let preferences = Preferences {
+            timezone: String::from("Europe/Berlin"),
+            ..Preferences::default()
+        };
+        // 23:30 UTC is already the next day in Berlin.
+        assert_eq!(
+            preferences.today(utc("2025-01-15T23:30:00Z")),
+            date(2025, 1, 16)
+        );
+        assert_eq!(
+            Preferences::default().today(utc("2025-01-15T23:30:00Z")),
+            date(2025, 1, 15)
+        );
+    }
+}
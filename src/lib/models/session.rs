@@ -0,0 +1,71 @@
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum::http::header::USER_AGENT;
+use axum::http::request::Parts;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use uuid::Uuid;
+
+/// Struct holding information about the client sending a request.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip: Option<IpAddr>,
+}
+
+impl ClientInfo {
+    pub fn new(user_agent: Option<String>, ip: Option<IpAddr>) -> Self {
+        Self { user_agent, ip }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ClientInfo {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let user_agent = parts
+            .headers
+            .get(USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from);
+        let ip = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        Ok(Self::new(user_agent, ip))
+    }
+}
+
+/// Struct holding a login session of a user.
+/// A session is the family of refresh tokens issued from one login.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Session {
+    /// Id of the session, which is the id of the token family.
+    pub id: Uuid,
+    /// Time of the login.
+    pub created_at: DateTime<Utc>,
+    /// Time of the last token refresh.
+    pub last_used_at: DateTime<Utc>,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+impl Session {
+    pub fn new(
+        id: Uuid,
+        created_at: DateTime<Utc>,
+        last_used_at: DateTime<Utc>,
+        user_agent: Option<String>,
+        ip: Option<String>,
+    ) -> Self {
+        Self {
+            id,
+            created_at,
+            last_used_at,
+            user_agent,
+            ip,
+        }
+    }
+}
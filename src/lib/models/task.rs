@@ -1,17 +1,83 @@
 use crate::utils::api_error_response::APIErrorResponse;
 use axum::http::StatusCode;
-use chrono::Utc;
+use chrono::{NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
 use uuid::Uuid;
 
+/// Number of tasks returned in a page when no limit is specified.
+pub const DEFAULT_TASK_LIMIT: i64 = 20;
+
+/// Maximum number of tasks that can be returned in a page.
+pub const MAX_TASK_LIMIT: i64 = 100;
+
+/// Lifecycle state of a task.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TaskStatus {
+    #[default]
+    Todo,
+    InProgress,
+    Done,
+    Cancelled,
+}
+
+impl TaskStatus {
+    /// Method returning the name of the status as stored in the database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskStatus::Todo => "todo",
+            TaskStatus::InProgress => "in-progress",
+            TaskStatus::Done => "done",
+            TaskStatus::Cancelled => "cancelled",
+        }
+    }
+
+    /// Method used to check whether a task can move from this status to `next`.
+    /// Open tasks can move to any other status, while finished tasks can only be reopened.
+    pub fn can_transition_to(&self, next: TaskStatus) -> bool {
+        match self {
+            TaskStatus::Todo | TaskStatus::InProgress => *self != next,
+            TaskStatus::Done | TaskStatus::Cancelled => next == TaskStatus::Todo,
+        }
+    }
+}
+
+impl fmt::Display for TaskStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for TaskStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "todo" => Ok(TaskStatus::Todo),
+            "in-progress" => Ok(TaskStatus::InProgress),
+            "done" => Ok(TaskStatus::Done),
+            "cancelled" => Ok(TaskStatus::Cancelled),
+            _ => Err(format!("Invalid task status {}", s)),
+        }
+    }
+}
+
 /// Struct holding task data.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Task {
     pub id: Uuid,
     pub name: String,
     pub description: String,
     pub priority: String,
     pub date: chrono::DateTime<Utc>,
+    /// Status of the task, ignored when updating a task.
+    #[serde(default)]
+    pub status: TaskStatus,
+    /// Time when the task was done, ignored when updating a task.
+    #[serde(default)]
+    pub completed_at: Option<chrono::DateTime<Utc>>,
 }
 
 impl Task {
@@ -21,6 +87,8 @@ impl Task {
         description: String,
         priority: String,
         data: chrono::DateTime<Utc>,
+        status: TaskStatus,
+        completed_at: Option<chrono::DateTime<Utc>>,
     ) -> Self {
         Self {
             id,
@@ -28,6 +96,8 @@ impl Task {
             description,
             priority,
             date: data,
+            status,
+            completed_at,
         }
     }
 
@@ -62,6 +132,8 @@ impl Task {
 pub struct TaskPayload {
     pub name: String,
     pub description: String,
+    /// Priority of the task, the default priority of the user if empty.
+    #[serde(default)]
     pub priority: String,
     pub date: chrono::DateTime<Utc>,
 }
@@ -96,13 +168,237 @@ impl TaskPayload {
             ));
         }
 
-        if self.priority.is_empty() {
+        None
+    }
+}
+
+/// Struct holding the requested status of a task.
+#[derive(Deserialize, Debug)]
+pub struct TaskStatusPayload {
+    pub status: TaskStatus,
+}
+
+/// Field used to sort listed tasks.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskSort {
+    #[default]
+    Date,
+    Priority,
+    Name,
+}
+
+/// Direction used to sort listed tasks.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Day of listed tasks, `today` or a date like `2025-03-15`.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "String")]
+pub enum TaskDay {
+    Today,
+    Date(NaiveDate),
+}
+
+impl TryFrom<String> for TaskDay {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if value == "today" {
+            return Ok(TaskDay::Today);
+        }
+
+        NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+            .map(TaskDay::Date)
+            .map_err(|_| format!("Invalid day {}", value))
+    }
+}
+
+/// Struct holding query parameters used for listing tasks.
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct TaskQuery {
+    /// Maximum number of tasks in the page.
+    pub limit: Option<i64>,
+    /// Id of the last task of the previous page.
+    pub cursor: Option<Uuid>,
+    pub priority: Option<String>,
+    /// Inclusive lower bound of the task date.
+    pub from: Option<chrono::DateTime<Utc>>,
+    /// Inclusive upper bound of the task date.
+    pub to: Option<chrono::DateTime<Utc>>,
+    /// Day of the tasks in the time zone of the user, replaced with `from` and `to` by the service.
+    pub day: Option<TaskDay>,
+    #[serde(default)]
+    pub sort: TaskSort,
+    #[serde(default)]
+    pub order: SortOrder,
+}
+
+impl TaskQuery {
+    /// Method returning the page size, falling back to `DEFAULT_TASK_LIMIT`.
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_TASK_LIMIT)
+    }
+
+    pub fn validate(&self) -> Option<APIErrorResponse> {
+        if !(1..=MAX_TASK_LIMIT).contains(&self.limit()) {
             return Some(APIErrorResponse::new(
                 StatusCode::BAD_REQUEST,
-                String::from("Priority cannot be empty"),
+                format!("Limit should be between 1 and {}", MAX_TASK_LIMIT),
             ));
         }
 
+        if self.day.is_some() && (self.from.is_some() || self.to.is_some()) {
+            return Some(APIErrorResponse::new(
+                StatusCode::BAD_REQUEST,
+                String::from("Day cannot be combined with from and to dates"),
+            ));
+        }
+
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if from > to {
+                return Some(APIErrorResponse::new(
+                    StatusCode::BAD_REQUEST,
+                    String::from("From date cannot be after to date"),
+                ));
+            }
+        }
+
         None
     }
 }
+
+/// Struct holding a page of tasks.
+#[derive(Serialize, Debug)]
+pub struct TaskPage {
+    pub tasks: Vec<Task>,
+    /// Cursor used to fetch the next page, `None` if this is the last page.
+    pub next_cursor: Option<Uuid>,
+    /// Number of tasks matching the filters across all pages.
+    pub total: i64,
+}
+
+impl TaskPage {
+    pub fn new(tasks: Vec<Task>, next_cursor: Option<Uuid>, total: i64) -> Self {
+        Self {
+            tasks,
+            next_cursor,
+            total,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_task_query_validate() {
+        let now = Utc::now();
+        let tests = vec![
+            (TaskQuery::default(), None),
+            (
+                TaskQuery {
+                    limit: Some(0),
+                    ..Default::default()
+                },
+                Some(APIErrorResponse::new(
+                    StatusCode::BAD_REQUEST,
+                    String::from("Limit should be between 1 and 100"),
+                )),
+            ),
+            (
+                TaskQuery {
+                    limit: Some(101),
+                    ..Default::default()
+                },
+                Some(APIErrorResponse::new(
+                    StatusCode::BAD_REQUEST,
+                    String::from("Limit should be between 1 and 100"),
+                )),
+            ),
+            (
+                TaskQuery {
+                    from: Some(now),
+                    to: Some(now - Duration::days(1)),
+                    ..Default::default()
+                },
+                Some(APIErrorResponse::new(
+                    StatusCode::BAD_REQUEST,
+                    String::from("From date cannot be after to date"),
+                )),
+            ),
+            (
+                TaskQuery {
+                    from: Some(now),
+                    day: Some(TaskDay::Today),
+                    ..Default::default()
+                },
+                Some(APIErrorResponse::new(
+                    StatusCode::BAD_REQUEST,
+                    String::from("Day cannot be combined with from and to dates"),
+                )),
+            ),
+        ];
+
+        for (query, expected) in tests {
+            assert_eq!(expected, query.validate());
+        }
+    }
+
+    #[test]
+    fn test_task_day() {
+        assert_eq!(TaskDay::try_from(String::from("today")), Ok(TaskDay::Today));
+        assert_eq!(
+            TaskDay::try_from(String::from("2025-03-15")),
+            Ok(TaskDay::Date(NaiveDate::from_ymd_opt(2025, 3, 15).unwrap()))
+        );
+        assert_eq!(
+            TaskDay::try_from(String::from("tomorrow")),
+            Err(String::from("Invalid day tomorrow"))
+        );
+    }
+
+    #[test]
+    fn test_task_status_transitions() {
+        use TaskStatus::*;
+
+        let tests = vec![
+            (Todo, InProgress, true),
+            (Todo, Done, true),
+            (Todo, Cancelled, true),
+            (Todo, Todo, false),
+            (InProgress, Todo, true),
+            (InProgress, Done, true),
+            (InProgress, InProgress, false),
+            (Done, Todo, true),
+            (Done, InProgress, false),
+            (Done, Cancelled, false),
+            (Cancelled, Todo, true),
+            (Cancelled, Done, false),
+        ];
+
+        for (from, to, expected) in tests {
+            assert_eq!(expected, from.can_transition_to(to), "{} -> {}", from, to);
+        }
+    }
+
+    #[test]
+    fn test_task_status_from_str() {
+        for status in [
+            TaskStatus::Todo,
+            TaskStatus::InProgress,
+            TaskStatus::Done,
+            TaskStatus::Cancelled,
+        ] {
+            assert_eq!(Ok(status), status.as_str().parse());
+        }
+        assert!("finished".parse::<TaskStatus>().is_err());
+    }
+}
@@ -1,10 +1,10 @@
 use serde::Serialize;
 
 /// Struct send by the server containing both tokens.
-#[derive(Serialize)]
+#[derive(Debug, Serialize)]
 pub struct TokenGroup {
-    access_token: String,
-    refresh_token: String,
+    pub access_token: String,
+    pub refresh_token: String,
 }
 
 impl TokenGroup {
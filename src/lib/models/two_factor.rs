@@ -0,0 +1,119 @@
+use crate::models::token_group::TokenGroup;
+use rand::rngs::OsRng;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Number of recovery codes generated when two-factor authentication is enabled.
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Characters used in recovery codes, without the ones that are easy to confuse.
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+/// Struct send by the server when two-factor authentication is set up.
+#[derive(Serialize)]
+pub struct TwoFactorSetup {
+    /// Base32 secret for entering in authenticator apps manually.
+    pub secret: String,
+    /// `otpauth://` uri, usually shown as QR code.
+    pub otpauth_uri: String,
+}
+
+/// Struct send by the server when two-factor authentication is enabled.
+/// The recovery codes are shown only once.
+#[derive(Serialize)]
+pub struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+impl RecoveryCodes {
+    /// Method that will generate new random recovery codes like `abcde-fghjk`.
+    pub fn generate() -> Self {
+        let recovery_codes = (0..RECOVERY_CODE_COUNT)
+            .map(|_| {
+                let code: String = (0..10)
+                    .map(|_| {
+                        RECOVERY_CODE_ALPHABET[OsRng.gen_range(0..RECOVERY_CODE_ALPHABET.len())]
+                            as char
+                    })
+                    .collect();
+                format!("{}-{}", &code[..5], &code[5..])
+            })
+            .collect();
+
+        Self { recovery_codes }
+    }
+}
+
+/// Function that will normalize recovery code entered by the user before hashing it,
+/// so that the case, spaces and dashes don't matter.
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// `TwoFactorCodePayload` holds TOTP code from authenticator app.
+#[derive(Deserialize)]
+pub struct TwoFactorCodePayload {
+    pub code: String,
+}
+
+/// `TwoFactorLoginPayload` holds the challenge token returned by login
+/// and either TOTP code or recovery code.
+#[derive(Deserialize)]
+pub struct TwoFactorLoginPayload {
+    pub challenge_token: String,
+    pub code: String,
+}
+
+/// `PasswordPayload` holds the password of the user, used to confirm sensitive actions.
+#[derive(Deserialize)]
+pub struct PasswordPayload {
+    pub password: String,
+}
+
+/// Struct send by the server when the password is correct but the user has to enter a code.
+#[derive(Debug, Serialize)]
+pub struct TwoFactorChallenge {
+    pub challenge_token: String,
+    pub two_factor_required: bool,
+}
+
+impl TwoFactorChallenge {
+    pub fn new(challenge_token: String) -> Self {
+        Self {
+            challenge_token,
+            two_factor_required: true,
+        }
+    }
+}
+
+/// Struct send by the server on login,
+/// either the tokens or challenge if the user has two-factor authentication enabled.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Tokens(TokenGroup),
+    Challenge(TwoFactorChallenge),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recovery_codes() {
+        let codes = RecoveryCodes::generate().recovery_codes;
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        for code in &codes {
+            assert_eq!(code.len(), 11);
+            assert_eq!(normalize_recovery_code(code).len(), 10);
+            assert_eq!(
+                normalize_recovery_code(&code.to_uppercase().replace('-', " ")),
+                normalize_recovery_code(code)
+            );
+        }
+        assert_ne!(codes[0], codes[1]);
+    }
+}
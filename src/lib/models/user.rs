@@ -1,6 +1,12 @@
+use crate::models::personal_access_token::PersonalAccessToken;
+use crate::models::policy::ValidationPolicy;
+use crate::models::preferences::Preferences;
+use crate::models::session::Session;
+use crate::models::task::Task;
 use crate::utils::api_error_response::APIErrorResponse;
 use axum::http::StatusCode;
-use serde::Deserialize;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 
 /// `UserPayload` holds used data from requests.
 #[derive(Deserialize)]
@@ -11,16 +17,16 @@ pub struct UserPayload {
 }
 
 impl UserPayload {
-    pub fn validate(&self) -> Option<APIErrorResponse> {
+    pub fn validate(&self, policy: &ValidationPolicy) -> Option<APIErrorResponse> {
         if let Some(error) = self.validate_email() {
             return Some(error);
         }
 
-        if let Some(error) = self.validate_username() {
+        if let Some(error) = self.validate_username(policy) {
             return Some(error);
         }
 
-        if let Some(error) = self.validate_password() {
+        if let Some(error) = self.validate_password(policy) {
             return Some(error);
         }
 
@@ -28,110 +34,196 @@ impl UserPayload {
     }
 
     fn validate_email(&self) -> Option<APIErrorResponse> {
-        let parts: Vec<&str> = self.email.split("@").collect();
-        if parts.len() != 2 {
-            return Some(APIErrorResponse::new(
-                StatusCode::BAD_REQUEST,
-                String::from("Invalid email format"),
-            ));
-        }
+        validate_email(&self.email)
+    }
 
-        if parts[0].is_empty() || parts[1].is_empty() {
-            return Some(APIErrorResponse::new(
-                StatusCode::BAD_REQUEST,
-                String::from("Invalid email format(missing local part or domain)"),
-            ));
-        }
+    fn validate_username(&self, policy: &ValidationPolicy) -> Option<APIErrorResponse> {
+        policy.username.validate(&self.username)
+    }
 
-        let domain_parts: Vec<&str> = parts[1].split(".").collect();
-        if domain_parts.len() < 2 {
-            return Some(APIErrorResponse::new(
-                StatusCode::BAD_REQUEST,
-                String::from("Invalid email format(invalid domain)"),
-            ));
-        }
+    fn validate_password(&self, policy: &ValidationPolicy) -> Option<APIErrorResponse> {
+        policy.password.validate(&self.password)
+    }
+}
 
-        if domain_parts[0].len() < 2 || domain_parts[1].len() < 2 {
-            return Some(APIErrorResponse::new(
-                StatusCode::BAD_REQUEST,
-                String::from("Invalid email format(invalid domain)"),
-            ));
+/// `UserUpdatePayload` holds the changes of the profile of the user, `None` fields are kept.
+#[derive(Deserialize)]
+pub struct UserUpdatePayload {
+    pub email: Option<String>,
+    pub username: Option<String>,
+}
+
+impl UserUpdatePayload {
+    pub fn validate(&self, policy: &ValidationPolicy) -> Option<APIErrorResponse> {
+        if let Some(error) = self.email.as_deref().and_then(validate_email) {
+            return Some(error);
         }
 
-        None
+        self.username
+            .as_deref()
+            .and_then(|username| policy.username.validate(username))
     }
+}
 
-    fn validate_username(&self) -> Option<APIErrorResponse> {
-        if self.username.len() < 8 {
-            return Some(APIErrorResponse::new(
-                StatusCode::BAD_REQUEST,
-                String::from("Username should be more than 7 characters"),
-            ));
-        }
+/// Function that will validate the format of the email.
+fn validate_email(email: &str) -> Option<APIErrorResponse> {
+    let parts: Vec<&str> = email.split("@").collect();
+    if parts.len() != 2 {
+        return Some(APIErrorResponse::new(
+            StatusCode::BAD_REQUEST,
+            String::from("Invalid email format"),
+        ));
+    }
 
-        None
+    if parts[0].is_empty() || parts[1].is_empty() {
+        return Some(APIErrorResponse::new(
+            StatusCode::BAD_REQUEST,
+            String::from("Invalid email format(missing local part or domain)"),
+        ));
     }
 
-    fn validate_password(&self) -> Option<APIErrorResponse> {
-        if self.password.len() < 8 {
-            return Some(APIErrorResponse::new(
-                StatusCode::BAD_REQUEST,
-                String::from("Password is too short"),
-            ));
-        }
+    let domain_parts: Vec<&str> = parts[1].split(".").collect();
+    if domain_parts.len() < 2 {
+        return Some(APIErrorResponse::new(
+            StatusCode::BAD_REQUEST,
+            String::from("Invalid email format(invalid domain)"),
+        ));
+    }
 
-        if !self.password.chars().any(|c| c.is_numeric()) {
-            return Some(APIErrorResponse::new(
-                StatusCode::BAD_REQUEST,
-                String::from("Password must contain at least one number"),
-            ));
-        }
+    if domain_parts[0].len() < 2 || domain_parts[1].len() < 2 {
+        return Some(APIErrorResponse::new(
+            StatusCode::BAD_REQUEST,
+            String::from("Invalid email format(invalid domain)"),
+        ));
+    }
 
-        if !self.password.chars().any(|c| c.is_uppercase()) {
-            return Some(APIErrorResponse::new(
-                StatusCode::BAD_REQUEST,
-                String::from("Password must contain at least one uppercase letter"),
-            ));
-        }
+    None
+}
 
-        if !self.password.chars().any(|c| c.is_lowercase()) {
-            return Some(APIErrorResponse::new(
-                StatusCode::BAD_REQUEST,
-                String::from("Password must contain at least one lowercase letter"),
-            ));
-        }
+/// `LoginPayload` holds the credentials used to log in.
+/// `identifier` is the email or the username of the user, `email` is accepted as its alias.
+#[derive(Deserialize)]
+pub struct LoginPayload {
+    #[serde(alias = "email")]
+    pub identifier: String,
+    pub password: String,
+}
+
+impl LoginPayload {
+    /// Method that will check if the identifier is an email, usernames can't contain @.
+    pub fn is_email(&self) -> bool {
+        self.identifier.contains('@')
+    }
+}
+
+/// `PasswordChangePayload` holds the data used to change the password.
+#[derive(Deserialize)]
+pub struct PasswordChangePayload {
+    pub current_password: String,
+    pub new_password: String,
+}
 
-        if !self.password.chars().any(|c| c.is_ascii_punctuation()) {
+impl PasswordChangePayload {
+    pub fn validate(&self, policy: &ValidationPolicy) -> Option<APIErrorResponse> {
+        if self.current_password == self.new_password {
             return Some(APIErrorResponse::new(
                 StatusCode::BAD_REQUEST,
-                String::from("Password must contain at least one special character"),
+                String::from("New password should be different from the current one"),
             ));
         }
 
-        None
+        policy.password.validate(&self.new_password)
+    }
+}
+
+/// `EmailPayload` holds the email of a user, used to request mails.
+#[derive(Deserialize)]
+pub struct EmailPayload {
+    pub email: String,
+}
+
+/// `PasswordResetPayload` holds the reset token sent by mail and the new password.
+#[derive(Deserialize)]
+pub struct PasswordResetPayload {
+    pub token: String,
+    pub new_password: String,
+}
+
+impl PasswordResetPayload {
+    pub fn validate(&self, policy: &ValidationPolicy) -> Option<APIErrorResponse> {
+        policy.password.validate(&self.new_password)
+    }
+}
+
+/// `UserDataExport` holds all data of the user, returned as JSON archive.
+#[derive(Serialize)]
+pub struct UserDataExport {
+    pub exported_at: DateTime<Utc>,
+    pub user: User,
+    pub preferences: Preferences,
+    pub sessions: Vec<Session>,
+    pub personal_access_tokens: Vec<PersonalAccessToken>,
+    pub tasks: Vec<Task>,
+}
+
+impl UserDataExport {
+    pub fn new(
+        user: User,
+        preferences: Preferences,
+        sessions: Vec<Session>,
+        personal_access_tokens: Vec<PersonalAccessToken>,
+        tasks: Vec<Task>,
+    ) -> Self {
+        Self {
+            exported_at: Utc::now(),
+            user,
+            preferences,
+            sessions,
+            personal_access_tokens,
+            tasks,
+        }
     }
 }
 
 /// `User` holds used data.
+/// The password hash and the TOTP secret are never serialized.
+#[derive(Clone, Serialize)]
 pub struct User {
     pub id: i32,
     pub email: String,
     pub username: String,
+    #[serde(skip)]
     pub password: String,
+    pub email_verified: bool,
+    /// Base32 TOTP secret, set when two-factor authentication is enabled or being set up.
+    #[serde(skip)]
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
 }
 
 impl User {
-    pub fn new(id: i32, email: String, username: String, password: String) -> Self {
+    pub fn new(
+        id: i32,
+        email: String,
+        username: String,
+        password: String,
+        email_verified: bool,
+        totp_secret: Option<String>,
+        totp_enabled: bool,
+    ) -> Self {
         Self {
             id,
             email,
             username,
             password,
+            email_verified,
+            totp_secret,
+            totp_enabled,
         }
     }
-
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
 
@@ -218,26 +310,45 @@ mod tests {
         ];
 
         for test in tests {
-            assert_eq!(test.expected, test.user_payload.validate());
+            assert_eq!(
+                test.expected,
+                test.user_payload.validate(&ValidationPolicy::default())
+            );
         }
     }
 
     #[test]
     fn test_user_validate_username() {
-        let tests = vec![TestCase::new(
-            UserPayload {
-                email: String::from("email@example.com"),
-                username: String::from("user"),
-                password: String::from("Password_0123"),
-            },
-            Some(APIErrorResponse::new(
-                StatusCode::BAD_REQUEST,
-                String::from("Username should be more than 7 characters"),
-            )),
-        )];
+        let tests = vec![
+            TestCase::new(
+                UserPayload {
+                    email: String::from("email@example.com"),
+                    username: String::from("user"),
+                    password: String::from("Password_0123"),
+                },
+                Some(APIErrorResponse::new(
+                    StatusCode::BAD_REQUEST,
+                    String::from("Username should be more than 7 characters"),
+                )),
+            ),
+            TestCase::new(
+                UserPayload {
+                    email: String::from("email@example.com"),
+                    username: String::from("user@example.com"),
+                    password: String::from("Password_0123"),
+                },
+                Some(APIErrorResponse::new(
+                    StatusCode::BAD_REQUEST,
+                    String::from("Username shouldn't contain @"),
+                )),
+            ),
+        ];
 
         for test in tests {
-            assert_eq!(test.expected, test.user_payload.validate());
+            assert_eq!(
+                test.expected,
+                test.user_payload.validate(&ValidationPolicy::default())
+            );
         }
     }
 
@@ -302,7 +413,76 @@ mod tests {
         ];
 
         for test in tests {
-            assert_eq!(test.expected, test.user_payload.validate());
+            assert_eq!(
+                test.expected,
+                test.user_payload.validate(&ValidationPolicy::default())
+            );
         }
     }
+
+    #[test]
+    fn test_user_update_validate() {
+        let payload = UserUpdatePayload {
+            email: None,
+            username: None,
+        };
+        assert_eq!(payload.validate(&ValidationPolicy::default()), None);
+
+        let payload = UserUpdatePayload {
+            email: Some(String::from("email@examplecom")),
+            username: Some(String::from("Valid_Username")),
+        };
+        assert_eq!(
+            payload.validate(&ValidationPolicy::default()),
+            Some(APIErrorResponse::new(
+                StatusCode::BAD_REQUEST,
+                String::from("Invalid email format(invalid domain)"),
+            ))
+        );
+
+        let payload = UserUpdatePayload {
+            email: Some(String::from("email@example.com")),
+            username: Some(String::from("user")),
+        };
+        assert_eq!(
+            payload.validate(&ValidationPolicy::default()),
+            Some(APIErrorResponse::new(
+                StatusCode::BAD_REQUEST,
+                String::from("Username should be more than 7 characters"),
+            ))
+        );
+    }
+
+    #[test]
+    fn test_password_change_validate() {
+        let payload = PasswordChangePayload {
+            current_password: String::from("Password_0123"),
+            new_password: String::from("Password_0123"),
+        };
+        assert_eq!(
+            payload.validate(&ValidationPolicy::default()),
+            Some(APIErrorResponse::new(
+                StatusCode::BAD_REQUEST,
+                String::from("New password should be different from the current one"),
+            ))
+        );
+
+        let payload = PasswordChangePayload {
+            current_password: String::from("Password_0123"),
+            new_password: String::from("password"),
+        };
+        assert_eq!(
+            payload.validate(&ValidationPolicy::default()),
+            Some(APIErrorResponse::new(
+                StatusCode::BAD_REQUEST,
+                String::from("Password must contain at least one number"),
+            ))
+        );
+
+        let payload = PasswordChangePayload {
+            current_password: String::from("Password_0123"),
+            new_password: String::from("Password_4567"),
+        };
+        assert_eq!(payload.validate(&ValidationPolicy::default()), None);
+    }
 }
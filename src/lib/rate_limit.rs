@@ -0,0 +1,304 @@
+//! `rate_limit` module used to limit the request rate of the clients.
+use crate::auth::AccessClaims;
+use crate::utils::api_error_response::APIErrorResponse;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use chrono::Duration;
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Maximum number of buckets, the full ones are dropped when it is reached.
+const MAX_BUCKETS: usize = 10_000;
+
+/// Shortest time between two sweeps of the full buckets.
+const SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Budget of requests of a client, `requests` can be sent at once
+/// and they are refilled evenly over `period`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub period: Duration,
+}
+
+impl RateLimit {
+    pub fn new(requests: u32, period: Duration) -> Self {
+        Self { requests, period }
+    }
+
+    /// Method that will return the number of requests refilled per second.
+    fn refill_rate(&self) -> f64 {
+        self.requests as f64 * 1000.0 / self.period.num_milliseconds() as f64
+    }
+}
+
+/// Struct holding the budgets of the endpoints.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimitSettings {
+    /// Budget of the user endpoints per IP.
+    pub auth: RateLimit,
+    /// Budget of the task endpoints per user.
+    pub tasks: RateLimit,
+}
+
+impl Default for RateLimitSettings {
+    fn default() -> Self {
+        Self {
+            auth: RateLimit::new(30, Duration::minutes(1)),
+            tasks: RateLimit::new(300, Duration::minutes(1)),
+        }
+    }
+}
+
+/// Outcome of a request checked by `RateLimiter`.
+#[derive(Debug, PartialEq)]
+pub struct RateLimitStatus {
+    /// Whether the request is allowed.
+    pub allowed: bool,
+    pub limit: u32,
+    /// Requests left in the bucket.
+    pub remaining: u32,
+    /// Seconds until the bucket is full again.
+    pub reset: u64,
+    /// Seconds until the next request is allowed, 0 if it is allowed now.
+    pub retry_after: u64,
+}
+
+/// Token bucket of a client.
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// Buckets of the clients with the keys in the order the buckets were created.
+#[derive(Default)]
+struct Buckets {
+    buckets: HashMap<String, Bucket>,
+    order: VecDeque<String>,
+    swept_at: Option<Instant>,
+}
+
+/// `RateLimiter` keeps a token bucket per client.
+/// The buckets are kept in memory, so each server instance has its own budget.
+#[derive(Clone)]
+pub struct RateLimiter {
+    limit: RateLimit,
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            buckets: Arc::new(Mutex::new(Buckets::default())),
+        }
+    }
+
+    /// Method that will take a request from the bucket of the client with `key` at `now`.
+    pub fn check(&self, key: &str, now: Instant) -> RateLimitStatus {
+        let capacity = self.limit.requests as f64;
+        let rate = self.limit.refill_rate();
+        let refill = |bucket: &Bucket| {
+            let elapsed = now.saturating_duration_since(bucket.updated_at);
+            (bucket.tokens + elapsed.as_secs_f64() * rate).min(capacity)
+        };
+
+        let mut state = self.buckets.lock().unwrap();
+        let Buckets {
+            buckets,
+            order,
+            swept_at,
+        } = &mut *state;
+        if buckets.len() >= MAX_BUCKETS && !buckets.contains_key(key) {
+            // Full buckets are the same as missing ones, the sweep runs at most once per interval
+            // so a flood of new clients doesn't scan all buckets on each request.
+            if swept_at
+                .is_none_or(|swept_at| now.saturating_duration_since(swept_at) >= SWEEP_INTERVAL)
+            {
+                buckets.retain(|_, bucket| refill(bucket) < capacity);
+                order.retain(|key| buckets.contains_key(key));
+                *swept_at = Some(now);
+            }
+
+            // The oldest bucket makes room when the sweep didn't.
+            if buckets.len() >= MAX_BUCKETS {
+                if let Some(oldest) = order.pop_front() {
+                    buckets.remove(&oldest);
+                }
+            }
+        }
+
+        let bucket = buckets.entry(String::from(key)).or_insert_with(|| {
+            order.push_back(String::from(key));
+            Bucket {
+                tokens: capacity,
+                updated_at: now,
+            }
+        });
+        bucket.tokens = refill(bucket);
+        bucket.updated_at = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+
+        let seconds = |tokens: f64| (tokens.max(0.0) / rate).ceil() as u64;
+        RateLimitStatus {
+            allowed,
+            limit: self.limit.requests,
+            remaining: bucket.tokens.floor() as u32,
+            reset: seconds(capacity - bucket.tokens),
+            retry_after: if allowed {
+                0
+            } else {
+                seconds(1.0 - bucket.tokens).max(1)
+            },
+        }
+    }
+}
+
+/// Function that will return the key of the client sending the request,
+/// the user id if the request has `AccessClaims` otherwise the IP.
+fn client_key(request: &Request) -> String {
+    if let Some(claims) = request.extensions().get::<AccessClaims>() {
+        return format!("user:{}", claims.sub);
+    }
+
+    match request.extensions().get::<ConnectInfo<SocketAddr>>() {
+        Some(ConnectInfo(addr)) => format!("ip:{}", addr.ip()),
+        None => String::from("ip:unknown"),
+    }
+}
+
+/// Function that will add the `RateLimit-*` headers of `status`.
+fn add_headers(headers: &mut HeaderMap, status: &RateLimitStatus) {
+    for (name, value) in [
+        ("ratelimit-limit", status.limit as u64),
+        ("ratelimit-remaining", status.remaining as u64),
+        ("ratelimit-reset", status.reset),
+    ] {
+        headers.insert(HeaderName::from_static(name), HeaderValue::from(value));
+    }
+}
+
+/// Middleware that will reject the request with `TOO_MANY_REQUESTS`
+/// if the client exceeded the budget of `limiter`.
+/// Layered inside `access_token_claims` it limits the users, otherwise the IPs.
+pub async fn rate_limit(
+    State(limiter): State<RateLimiter>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let status = limiter.check(&client_key(&request), Instant::now());
+
+    let mut response = if status.allowed {
+        next.run(request).await
+    } else {
+        APIErrorResponse::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            String::from("Too many requests"),
+        )
+        .with_retry_after(status.retry_after)
+        .into_response()
+    };
+
+    add_headers(response.headers_mut(), &status);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_check() {
+        let limiter = RateLimiter::new(RateLimit::new(2, Duration::seconds(10)));
+        let now = Instant::now();
+
+        assert_eq!(
+            limiter.check("a", now),
+            RateLimitStatus {
+                allowed: true,
+                limit: 2,
+                remaining: 1,
+                reset: 5,
+                retry_after: 0,
+            }
+        );
+        assert!(limiter.check("a", now).allowed);
+        assert_eq!(
+            limiter.check("a", now),
+            RateLimitStatus {
+                allowed: false,
+                limit: 2,
+                remaining: 0,
+                reset: 10,
+                retry_after: 5,
+            }
+        );
+
+        // Other clients have their own bucket.
+        assert!(limiter.check("b", now).allowed);
+
+        // A request is refilled each 5 seconds.
+        let later = now + std::time::Duration::from_secs(5);
+        assert!(limiter.check("a", later).allowed);
+        assert!(!limiter.check("a", later).allowed);
+    }
+
+    #[test]
+    fn test_max_buckets() {
+        let limiter = RateLimiter::new(RateLimit::new(2, Duration::seconds(10)));
+        let now = Instant::now();
+        for key in 0..MAX_BUCKETS {
+            limiter.check(&key.to_string(), now);
+        }
+
+        // None of the buckets is full, so the oldest one is dropped for the new client.
+        assert!(limiter.check("new", now).allowed);
+        {
+            let state = limiter.buckets.lock().unwrap();
+            assert_eq!(state.buckets.len(), MAX_BUCKETS);
+            assert!(!state.buckets.contains_key("0"));
+            assert_eq!(state.order.len(), MAX_BUCKETS);
+        }
+
+        // Once the buckets are full again they are swept.
+        let later = now + std::time::Duration::from_secs(10);
+        assert!(limiter.check("newer", later).allowed);
+        let state = limiter.buckets.lock().unwrap();
+        assert_eq!(state.buckets.len(), 1);
+        assert_eq!(state.order, VecDeque::from([String::from("newer")]));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let limiter = RateLimiter::new(RateLimit::new(1, Duration::minutes(1)));
+        let router = axum::Router::new()
+            .route("/", axum::routing::get(|| async { StatusCode::OK }))
+            .layer(axum::middleware::from_fn_with_state(limiter, rate_limit));
+        let request = || {
+            Request::builder()
+                .uri("/")
+                .body(axum::body::Body::empty())
+                .unwrap()
+        };
+
+        let response = router.clone().oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["ratelimit-limit"], "1");
+        assert_eq!(response.headers()["ratelimit-remaining"], "0");
+        assert_eq!(response.headers()["ratelimit-reset"], "60");
+
+        let response = router.oneshot(request()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["retry-after"], "60");
+        assert_eq!(response.headers()["ratelimit-remaining"], "0");
+    }
+}
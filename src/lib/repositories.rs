@@ -1,3 +1,7 @@
 pub mod token;
 pub mod user;
 pub mod task;
+pub mod one_time_token;
+pub mod personal_access_token;
+pub mod login_attempt;
+pub mod preferences;
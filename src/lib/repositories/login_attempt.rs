@@ -0,0 +1,126 @@
+use chrono::{DateTime, Utc};
+use sqlx::{query, Error as SQLError, PgPool, Row};
+use std::future::Future;
+
+/// `LoginAttemptRepository` manages the failed login attempts per account and per IP,
+/// identified by a key like `account:email@example.com` or `ip:127.0.0.1`.
+pub trait LoginAttemptRepository: Send + Sync + Clone + 'static {
+    /// Method that will fetch the latest time until which any of the keys is locked.
+    ///
+    /// # Errors
+    /// It can return any error related to database connection.
+    ///
+    /// # Returns
+    /// The end of the lockout, or `None` if none of the keys is locked.
+    fn get_locked_until(
+        &self,
+        keys: &[String],
+    ) -> impl Future<Output = Result<Option<DateTime<Utc>>, SQLError>> + Send;
+
+    /// Method that will record failed login attempt of a key.
+    /// The failures before `since` are forgotten.
+    ///
+    /// # Errors
+    /// It can return any error related to database connection.
+    ///
+    /// # Returns
+    /// The number of failures of the key since the count was reset.
+    fn add_failure(
+        &self,
+        key: &str,
+        since: DateTime<Utc>,
+    ) -> impl Future<Output = Result<i32, SQLError>> + Send;
+
+    /// Method that will lock a key until `locked_until` and record the lockout event.
+    ///
+    /// # Errors
+    /// It can return any error related to database connection.
+    fn lock(
+        &self,
+        key: &str,
+        failures: i32,
+        locked_until: DateTime<Utc>,
+    ) -> impl Future<Output = Result<(), SQLError>> + Send;
+
+    /// Method that will forget the failed login attempts of a key.
+    ///
+    /// # Errors
+    /// It can return any error related to database connection.
+    fn clear_failures(&self, key: &str) -> impl Future<Output = Result<(), SQLError>> + Send;
+}
+
+/// `PostgresLoginAttemptRepository` is implementation of `LoginAttemptRepository` with postgres.
+#[derive(Clone)]
+pub struct PostgresLoginAttemptRepository {
+    db: PgPool,
+}
+
+impl PostgresLoginAttemptRepository {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+impl LoginAttemptRepository for PostgresLoginAttemptRepository {
+    async fn get_locked_until(&self, keys: &[String]) -> Result<Option<DateTime<Utc>>, SQLError> {
+        let row = query(
+            "SELECT MAX(locked_until) FROM login_failures \
+             WHERE key = ANY($1) AND locked_until > NOW()",
+        )
+        .bind(keys)
+        .fetch_one(&self.db)
+        .await?;
+
+        row.try_get(0)
+    }
+
+    async fn add_failure(&self, key: &str, since: DateTime<Utc>) -> Result<i32, SQLError> {
+        let row = query(
+            "INSERT INTO login_failures(key, failures, last_failure_at) VALUES ($1, 1, NOW()) \
+             ON CONFLICT (key) DO UPDATE SET \
+             failures = CASE WHEN login_failures.last_failure_at < $2 THEN 1 \
+             ELSE login_failures.failures + 1 END, \
+             last_failure_at = NOW() \
+             RETURNING failures",
+        )
+        .bind(key)
+        .bind(since)
+        .fetch_one(&self.db)
+        .await?;
+
+        row.try_get(0)
+    }
+
+    async fn lock(
+        &self,
+        key: &str,
+        failures: i32,
+        locked_until: DateTime<Utc>,
+    ) -> Result<(), SQLError> {
+        let mut tx = self.db.begin().await?;
+
+        query("UPDATE login_failures SET locked_until = $2 WHERE key = $1")
+            .bind(key)
+            .bind(locked_until)
+            .execute(&mut *tx)
+            .await?;
+
+        query("INSERT INTO lockout_events(key, failures, locked_until) VALUES ($1, $2, $3)")
+            .bind(key)
+            .bind(failures)
+            .bind(locked_until)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await
+    }
+
+    async fn clear_failures(&self, key: &str) -> Result<(), SQLError> {
+        query("DELETE FROM login_failures WHERE key = $1")
+            .bind(key)
+            .execute(&self.db)
+            .await?;
+
+        Ok(())
+    }
+}
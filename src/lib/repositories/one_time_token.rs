@@ -0,0 +1,93 @@
+use crate::models::one_time_token::TokenPurpose;
+use chrono::{DateTime, Utc};
+use sqlx::{query, Error as SQLError, PgPool, Row};
+use std::future::Future;
+
+/// `OneTimeTokenRepository` manages single use tokens sent to the users by mail.
+pub trait OneTimeTokenRepository: Send + Sync + Clone + 'static {
+    /// Method that will add token with specified hash.
+    /// The unused tokens of the user for the same purpose are deleted, so only the latest token works.
+    ///
+    /// # Errors
+    /// It can return any error related to database connection.
+    fn add_token(
+        &self,
+        hash: &str,
+        user_id: i32,
+        purpose: TokenPurpose,
+        expires_at: DateTime<Utc>,
+    ) -> impl Future<Output = Result<(), SQLError>> + Send;
+
+    /// Method that will mark token with specified hash as used.
+    /// The token is used only if it is unused, unexpired and has the specified purpose.
+    ///
+    /// # Errors
+    /// It can return any error related to database connection.
+    ///
+    /// # Returns
+    /// The id of the user of the token, or `None` if it can't be used.
+    fn use_token(
+        &self,
+        hash: &str,
+        purpose: TokenPurpose,
+    ) -> impl Future<Output = Result<Option<i32>, SQLError>> + Send;
+}
+
+/// `PostgresOneTimeTokenRepository` is implementation of `OneTimeTokenRepository` with postgres.
+#[derive(Clone)]
+pub struct PostgresOneTimeTokenRepository {
+    db: PgPool,
+}
+
+impl PostgresOneTimeTokenRepository {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+impl OneTimeTokenRepository for PostgresOneTimeTokenRepository {
+    async fn add_token(
+        &self,
+        hash: &str,
+        user_id: i32,
+        purpose: TokenPurpose,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), SQLError> {
+        let mut tx = self.db.begin().await?;
+
+        query(
+            "DELETE FROM one_time_tokens WHERE user_id = $1 AND purpose = $2 AND used_at IS NULL",
+        )
+        .bind(user_id)
+        .bind(purpose.as_str())
+        .execute(&mut *tx)
+        .await?;
+
+        query("INSERT INTO one_time_tokens(hash, user_id, purpose, expires_at) VALUES ($1, $2, $3, $4)")
+            .bind(hash)
+            .bind(user_id)
+            .bind(purpose.as_str())
+            .bind(expires_at)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await
+    }
+
+    async fn use_token(&self, hash: &str, purpose: TokenPurpose) -> Result<Option<i32>, SQLError> {
+        let result = query(
+            "UPDATE one_time_tokens SET used_at = NOW() \
+             WHERE hash = $1 AND purpose = $2 AND used_at IS NULL AND expires_at > NOW() \
+             RETURNING user_id",
+        )
+        .bind(hash)
+        .bind(purpose.as_str())
+        .fetch_optional(&self.db)
+        .await?;
+
+        match result {
+            Some(row) => Ok(Some(row.try_get(0)?)),
+            None => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use crate::auth::Scope;
+use crate::models::personal_access_token::PersonalAccessToken;
+use sqlx::postgres::PgRow;
+use sqlx::{query, Error as SQLError, PgPool, Row};
+use std::future::Future;
+use uuid::Uuid;
+
+/// `PersonalAccessTokenRepository` manages personal access tokens of the users.
+pub trait PersonalAccessTokenRepository: Send + Sync + Clone + 'static {
+    /// Method that will add personal access token with specified hash.
+    ///
+    /// # Errors
+    /// It can return any error related to database connection.
+    fn add_token(
+        &self,
+        token: &PersonalAccessToken,
+        hash: &str,
+    ) -> impl Future<Output = Result<(), SQLError>> + Send;
+
+    /// Method that will fetch all personal access tokens of a user, including the expired ones.
+    ///
+    /// # Errors
+    /// It can return any error related to database connection.
+    fn get_tokens_by_user_id(
+        &self,
+        user_id: i32,
+    ) -> impl Future<Output = Result<Vec<PersonalAccessToken>, SQLError>> + Send;
+
+    /// Method that will delete personal access token with specified id of a user.
+    ///
+    /// # Errors
+    /// It can return any error related to database connection.
+    ///
+    /// # Returns
+    /// True if token was deleted otherwise false.
+    fn delete_token(
+        &self,
+        id: Uuid,
+        user_id: i32,
+    ) -> impl Future<Output = Result<bool, SQLError>> + Send;
+
+    /// Method that will record the use of unexpired personal access token with specified hash.
+    ///
+    /// # Errors
+    /// It can return any error related to database connection.
+    ///
+    /// # Returns
+    /// The token, or `None` if it doesn't exist or is expired.
+    fn use_token(
+        &self,
+        hash: &str,
+    ) -> impl Future<Output = Result<Option<PersonalAccessToken>, SQLError>> + Send;
+}
+
+/// Columns selected to build `PersonalAccessToken` with `token_from_row`.
+const TOKEN_COLUMNS: &str = "id, user_id, name, scopes, created_at, expires_at, last_used_at";
+
+/// Function that will build `PersonalAccessToken` from a row with `TOKEN_COLUMNS`.
+/// Unknown scopes are ignored.
+fn token_from_row(row: &PgRow) -> Result<PersonalAccessToken, SQLError> {
+    let scopes: Vec<String> = row.try_get("scopes")?;
+
+    Ok(PersonalAccessToken::new(
+        row.try_get("id")?,
+        row.try_get("user_id")?,
+        row.try_get("name")?,
+        scopes
+            .iter()
+            .filter_map(|scope| Scope::parse(scope))
+            .collect(),
+        row.try_get("created_at")?,
+        row.try_get("expires_at")?,
+        row.try_get("last_used_at")?,
+    ))
+}
+
+/// `PostgresPersonalAccessTokenRepository` is implementation of `PersonalAccessTokenRepository` with postgres.
+#[derive(Clone)]
+pub struct PostgresPersonalAccessTokenRepository {
+    db: PgPool,
+}
+
+impl PostgresPersonalAccessTokenRepository {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+impl PersonalAccessTokenRepository for PostgresPersonalAccessTokenRepository {
+    async fn add_token(&self, token: &PersonalAccessToken, hash: &str) -> Result<(), SQLError> {
+        let scopes: Vec<&str> = token.scopes.iter().map(Scope::as_str).collect();
+
+        query(
+            "INSERT INTO personal_access_tokens(id, user_id, name, hash, scopes, created_at, expires_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(token.id)
+        .bind(token.user_id)
+        .bind(&token.name)
+        .bind(hash)
+        .bind(scopes)
+        .bind(token.created_at)
+        .bind(token.expires_at)
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_tokens_by_user_id(
+        &self,
+        user_id: i32,
+    ) -> Result<Vec<PersonalAccessToken>, SQLError> {
+        let rows = query(&format!(
+            "SELECT {} FROM personal_access_tokens WHERE user_id = $1 ORDER BY created_at",
+            TOKEN_COLUMNS
+        ))
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        rows.iter().map(token_from_row).collect()
+    }
+
+    async fn delete_token(&self, id: Uuid, user_id: i32) -> Result<bool, SQLError> {
+        let result = query("DELETE FROM personal_access_tokens WHERE id = $1 AND user_id = $2")
+            .bind(id)
+            .bind(user_id)
+            .execute(&self.db)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn use_token(&self, hash: &str) -> Result<Option<PersonalAccessToken>, SQLError> {
+        let row = query(&format!(
+            "UPDATE personal_access_tokens SET last_used_at = NOW() \
+             WHERE hash = $1 AND (expires_at IS NULL OR expires_at > NOW()) \
+             RETURNING {}",
+            TOKEN_COLUMNS
+        ))
+        .bind(hash)
+        .fetch_optional(&self.db)
+        .await?;
+
+        row.as_ref().map(token_from_row).transpose()
+    }
+}
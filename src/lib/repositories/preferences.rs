@@ -0,0 +1,93 @@
+use crate::models::preferences::{Preferences, WeekStart};
+use sqlx::{query, Error as SQLError, PgPool, Row};
+use std::future::Future;
+
+/// `PreferencesRepository` manages the preferences of the users.
+pub trait PreferencesRepository: Send + Sync + Clone + 'static {
+    /// Method that will fetch the preferences of a user.
+    ///
+    /// # Errors
+    /// It can return any error related to database connection.
+    ///
+    /// # Returns
+    /// The preferences, or the default ones if the user hasn't set any.
+    fn get_preferences(
+        &self,
+        user_id: i32,
+    ) -> impl Future<Output = Result<Preferences, SQLError>> + Send;
+
+    /// Method that will set the preferences of a user.
+    ///
+    /// # Errors
+    /// It can return any error related to database connection.
+    ///
+    /// # Returns
+    /// False if the default priority doesn't exist, then nothing is set.
+    fn set_preferences(
+        &self,
+        user_id: i32,
+        preferences: &Preferences,
+    ) -> impl Future<Output = Result<bool, SQLError>> + Send;
+}
+
+/// `PostgresPreferencesRepository` is implementation of `PreferencesRepository` with postgres.
+#[derive(Clone)]
+pub struct PostgresPreferencesRepository {
+    db: PgPool,
+}
+
+impl PostgresPreferencesRepository {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+impl PreferencesRepository for PostgresPreferencesRepository {
+    async fn get_preferences(&self, user_id: i32) -> Result<Preferences, SQLError> {
+        let row = query(
+            "SELECT timezone, locale, week_start, default_priority FROM user_preferences \
+             WHERE user_id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(Preferences::default());
+        };
+
+        let week_start: String = row.try_get(2)?;
+        let week_start = week_start
+            .parse::<WeekStart>()
+            .map_err(|err| SQLError::Decode(err.into()))?;
+
+        Ok(Preferences::new(
+            row.try_get(0)?,
+            row.try_get(1)?,
+            week_start,
+            row.try_get(3)?,
+        ))
+    }
+
+    async fn set_preferences(
+        &self,
+        user_id: i32,
+        preferences: &Preferences,
+    ) -> Result<bool, SQLError> {
+        let result = query(
+            "INSERT INTO user_preferences(user_id, timezone, locale, week_start, default_priority) \
+             SELECT $1, $2, $3, $4, $5 WHERE EXISTS (SELECT 1 FROM priorities WHERE priority = $5) \
+             ON CONFLICT (user_id) DO UPDATE SET \
+             timezone = $2, locale = $3, week_start = $4, default_priority = $5",
+        )
+        .bind(user_id)
+        .bind(&preferences.timezone)
+        .bind(&preferences.locale)
+        .bind(preferences.week_start.as_str())
+        .bind(&preferences.default_priority)
+        .execute(&self.db)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -1,8 +1,9 @@
-use crate::models::task::Task;
+use crate::models::task::{SortOrder, Task, TaskQuery, TaskSort, TaskStatus};
 use chrono::{DateTime, Utc};
 use sqlx::error::Error as SQLXError;
-use sqlx::PgPool;
+use sqlx::postgres::PgRow;
 use sqlx::{query, Row};
+use sqlx::{PgPool, Postgres, QueryBuilder};
 use std::future::Future;
 use uuid::Uuid;
 
@@ -21,17 +22,66 @@ pub trait TaskRepository: Send + Sync + Clone + 'static {
         priority: &str,
     ) -> impl Future<Output = Result<bool, sqlx::Error>> + Send;
 
-    /// Method used to get all tasks linked to a user.
+    /// Method used to get a page of tasks linked to a user.
+    /// The tasks are filtered and sorted as specified in `filter` and start after its cursor.
     fn get_tasks_by_user_id(
         &self,
         user_id: i32,
+        filter: &TaskQuery,
+        limit: i64,
     ) -> impl Future<Output = Result<Vec<Task>, sqlx::Error>> + Send;
 
-    /// Method that will update existing task.
-    fn update_task(&self, task: &Task) -> impl Future<Output = Result<bool, SQLXError>> + Send;
+    /// Method used to count all tasks linked to a user matching the filters in `filter`.
+    /// The cursor is ignored.
+    fn count_tasks_by_user_id(
+        &self,
+        user_id: i32,
+        filter: &TaskQuery,
+    ) -> impl Future<Output = Result<i64, sqlx::Error>> + Send;
+
+    /// Method used to get a task with specified id linked to a user.
+    ///
+    /// # Errors
+    /// `RowNotFound` if the task doesn't exist or isn't linked to the user.
+    fn get_task_by_id(
+        &self,
+        id: Uuid,
+        user_id: i32,
+    ) -> impl Future<Output = Result<Task, SQLXError>> + Send;
+
+    /// Method that will update existing task owned by a user.
+    ///
+    /// # Returns
+    /// True if the task was updated otherwise false.
+    fn update_task(
+        &self,
+        task: &Task,
+        user_id: i32,
+    ) -> impl Future<Output = Result<bool, SQLXError>> + Send;
 
-    /// Method that will delete existing task with specified id.
-    fn delete_task_by_id(&self, id: Uuid) -> impl Future<Output = Result<bool, SQLXError>> + Send;
+    /// Method that will change the status of a task owned by a user.
+    /// The status is changed only if the task is still in status `from`.
+    ///
+    /// # Returns
+    /// True if the task was updated otherwise false.
+    fn update_task_status(
+        &self,
+        id: Uuid,
+        user_id: i32,
+        from: TaskStatus,
+        to: TaskStatus,
+        completed_at: Option<DateTime<Utc>>,
+    ) -> impl Future<Output = Result<bool, SQLXError>> + Send;
+
+    /// Method that will delete existing task with specified id owned by a user.
+    ///
+    /// # Returns
+    /// True if the task was deleted otherwise false.
+    fn delete_task_by_id(
+        &self,
+        id: Uuid,
+        user_id: i32,
+    ) -> impl Future<Output = Result<bool, SQLXError>> + Send;
 }
 
 /// Repository that implements `TaskRepository` using postgres.
@@ -44,19 +94,75 @@ impl PostgresTaskRepository {
     pub fn new(db: PgPool) -> Self {
         Self { db }
     }
+
+    /// Method that will create `Task` from a row with the columns
+    /// `id, name, description, priority, date, status, completed_at`.
+    fn task_from_row(row: &PgRow) -> Result<Task, SQLXError> {
+        let id: Uuid = row.try_get(0)?;
+        let name: String = row.try_get(1)?;
+        let description: String = row.try_get(2)?;
+        let priority: String = row.try_get(3)?;
+        let date: DateTime<Utc> = row.try_get(4)?;
+        let status: String = row.try_get(5)?;
+        let completed_at: Option<DateTime<Utc>> = row.try_get(6)?;
+
+        let status = status
+            .parse::<TaskStatus>()
+            .map_err(|err| SQLXError::Decode(err.into()))?;
+
+        Ok(Task::new(
+            id,
+            name,
+            description,
+            priority,
+            date,
+            status,
+            completed_at,
+        ))
+    }
+
+    /// Expression used to sort the tasks.
+    fn sort_expression(sort: TaskSort) -> &'static str {
+        match sort {
+            TaskSort::Date => "t.date",
+            TaskSort::Priority => "COALESCE(p.rank, 0)",
+            TaskSort::Name => "t.name",
+        }
+    }
+
+    /// Method that will push the `WHERE` clause matching the filters in `filter`.
+    fn push_filters(builder: &mut QueryBuilder<'_, Postgres>, user_id: i32, filter: &TaskQuery) {
+        builder.push(" WHERE t.user_id = ").push_bind(user_id);
+
+        if let Some(priority) = &filter.priority {
+            builder
+                .push(" AND t.priority = ")
+                .push_bind(priority.clone());
+        }
+
+        if let Some(from) = filter.from {
+            builder.push(" AND t.date >= ").push_bind(from);
+        }
+
+        if let Some(to) = filter.to {
+            builder.push(" AND t.date <= ").push_bind(to);
+        }
+    }
 }
 
 impl TaskRepository for PostgresTaskRepository {
     async fn add_task(&self, task: &Task, user_id: i32) -> Result<(), SQLXError> {
         println!("Executing query for adding task with task {:?}", task);
 
-        query("INSERT INTO tasks (id, name, description, priority, date, user_id) VALUES ($1, $2, $3, $4, $5, $6)")
-            .bind(&task.id)
+        query("INSERT INTO tasks (id, name, description, priority, date, status, completed_at, user_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)")
+            .bind(task.id)
             .bind(&task.name)
             .bind(&task.description)
             .bind(&task.priority)
-            .bind(&task.date)
-            .bind(&user_id)
+            .bind(task.date)
+            .bind(task.status.as_str())
+            .bind(task.completed_at)
+            .bind(user_id)
             .execute(&self.db)
             .await?;
 
@@ -74,45 +180,121 @@ impl TaskRepository for PostgresTaskRepository {
         Ok(count > 0)
     }
 
-    async fn get_tasks_by_user_id(&self, user_id: i32) -> Result<Vec<Task>, SQLXError> {
-        let rows =
-            query("SELECT id, name, description, priority, date FROM tasks WHERE user_id = $1")
-                .bind(user_id)
-                .fetch_all(&self.db)
-                .await?;
+    async fn get_tasks_by_user_id(
+        &self,
+        user_id: i32,
+        filter: &TaskQuery,
+        limit: i64,
+    ) -> Result<Vec<Task>, SQLXError> {
+        let sort = Self::sort_expression(filter.sort);
+        let (comparison, direction) = match filter.order {
+            SortOrder::Asc => (">", "ASC"),
+            SortOrder::Desc => ("<", "DESC"),
+        };
+
+        let mut builder = QueryBuilder::new(
+            "SELECT t.id, t.name, t.description, t.priority, t.date, t.status, t.completed_at FROM tasks t \
+            LEFT JOIN priorities p ON p.priority = t.priority",
+        );
+        Self::push_filters(&mut builder, user_id, filter);
+
+        if let Some(cursor) = filter.cursor {
+            builder
+                .push(format!(
+                    " AND ({}, t.id) {} (SELECT {}, t.id FROM tasks t ",
+                    sort, comparison, sort
+                ))
+                .push("LEFT JOIN priorities p ON p.priority = t.priority WHERE t.id = ")
+                .push_bind(cursor)
+                .push(" AND t.user_id = ")
+                .push_bind(user_id)
+                .push(")");
+        }
+
+        builder
+            .push(format!(
+                " ORDER BY {} {}, t.id {} LIMIT ",
+                sort, direction, direction
+            ))
+            .push_bind(limit);
+
+        let rows = builder.build().fetch_all(&self.db).await?;
 
         let mut result: Vec<Task> = Vec::with_capacity(rows.len());
 
         for row in rows {
-            let id: Uuid = row.try_get(0)?;
-            let name: String = row.try_get(1)?;
-            let description: String = row.try_get(2)?;
-            let priority: String = row.try_get(3)?;
-            let date: DateTime<Utc> = row.try_get(4)?;
-            result.push(Task::new(id, name, description, priority, date))
+            result.push(Self::task_from_row(&row)?)
         }
 
         Ok(result)
     }
 
-    async fn update_task(&self, task: &Task) -> Result<bool, SQLXError> {
+    async fn count_tasks_by_user_id(
+        &self,
+        user_id: i32,
+        filter: &TaskQuery,
+    ) -> Result<i64, SQLXError> {
+        let mut builder = QueryBuilder::new("SELECT COUNT(*) FROM tasks t");
+        Self::push_filters(&mut builder, user_id, filter);
+
+        let row = builder.build().fetch_one(&self.db).await?;
+        row.try_get(0)
+    }
+
+    async fn get_task_by_id(&self, id: Uuid, user_id: i32) -> Result<Task, SQLXError> {
+        let row = query(
+            "SELECT id, name, description, priority, date, status, completed_at FROM tasks WHERE id = $1 AND user_id = $2",
+        )
+        .bind(id)
+        .bind(user_id)
+        .fetch_one(&self.db)
+        .await?;
+
+        Self::task_from_row(&row)
+    }
+
+    async fn update_task(&self, task: &Task, user_id: i32) -> Result<bool, SQLXError> {
         let result = query(
-            "UPDATE tasks SET name = $1, description = $2, priority = $3, date = $4 WHERE id = $5",
+            "UPDATE tasks SET name = $1, description = $2, priority = $3, date = $4 WHERE id = $5 AND user_id = $6",
         )
         .bind(&task.name)
         .bind(&task.description)
         .bind(&task.priority)
-        .bind(&task.date)
+        .bind(task.date)
         .bind(task.id)
+        .bind(user_id)
+        .execute(&self.db)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn update_task_status(
+        &self,
+        id: Uuid,
+        user_id: i32,
+        from: TaskStatus,
+        to: TaskStatus,
+        completed_at: Option<DateTime<Utc>>,
+    ) -> Result<bool, SQLXError> {
+        let result = query(
+            "UPDATE tasks SET status = $1, completed_at = $2 WHERE id = $3 AND user_id = $4 AND status = $5",
+        )
+        .bind(to.as_str())
+        .bind(completed_at)
+        .bind(id)
+        .bind(user_id)
+        .bind(from.as_str())
         .execute(&self.db)
         .await?;
 
         Ok(result.rows_affected() > 0)
     }
 
-    async fn delete_task_by_id(&self, id: Uuid) -> Result<bool, SQLXError> {
-        let result = query("DELETE FROM tasks WHERE id = $1")
+    async fn delete_task_by_id(&self, id: Uuid, user_id: i32) -> Result<bool, SQLXError> {
+        let result = query("DELETE FROM tasks WHERE id = $1 AND user_id = $2")
             .bind(id)
+            .bind(user_id)
             .execute(&self.db)
             .await?;
 
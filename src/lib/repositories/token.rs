@@ -1,21 +1,67 @@
-use chrono::NaiveDateTime;
-use sqlx::{query, Error as SQLError, PgPool};
+use crate::models::session::{ClientInfo, Session};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sqlx::{query, Error as SQLError, PgPool, Row};
 use std::future::Future;
 use uuid::Uuid;
 
+/// Outcome of using a refresh token for rotation.
+#[derive(Debug, PartialEq)]
+pub enum TokenUse {
+    /// The token was unused and is now marked as used.
+    Rotated { family_id: Uuid },
+    /// The token was already used, which means it was replayed.
+    Reused { family_id: Uuid },
+    /// The token doesn't exist (it expired or was revoked).
+    NotFound,
+}
+
 /// `TokenRepository` manages token data.
 pub trait TokenRepository: Send + Sync + Clone + 'static {
-    /// Method that will add a refresh token.
+    /// Method that will add a refresh token to a token family.
+    /// All tokens issued by rotating a token of a login share the same family.
+    /// The token keeps the creation time of the family and the client it was issued to.
     ///
     /// # Errors
     /// It can return any error related to database connection.
     fn add_token(
         &self,
         id: Uuid,
+        family_id: Uuid,
         exp: NaiveDateTime,
         user_id: i32,
+        client: &ClientInfo,
     ) -> impl Future<Output = Result<(), SQLError>> + Send;
 
+    /// Method that will mark token with specified id as used.
+    /// Used tokens are kept until they expire so that replaying them can be detected.
+    ///
+    /// # Errors
+    /// It can return any error related to database connection.
+    fn use_token(&self, id: Uuid) -> impl Future<Output = Result<TokenUse, SQLError>> + Send;
+
+    /// Method that will delete all tokens of a token family of a user.
+    ///
+    /// # Errors
+    /// It can return any error related to database connection.
+    ///
+    /// # Returns
+    /// The number of deleted tokens.
+    fn delete_token_family(
+        &self,
+        family_id: Uuid,
+        user_id: i32,
+    ) -> impl Future<Output = Result<u64, SQLError>> + Send;
+
+    /// Method that will fetch the active sessions of a user,
+    /// represented by their unused and unexpired tokens.
+    ///
+    /// # Errors
+    /// It can return any error related to database connection.
+    fn get_sessions_by_user_id(
+        &self,
+        user_id: i32,
+    ) -> impl Future<Output = Result<Vec<Session>, SQLError>> + Send;
+
     /// Method that will delete token with specified id.
     ///
     /// # Errors
@@ -24,6 +70,18 @@ pub trait TokenRepository: Send + Sync + Clone + 'static {
     /// # Returns
     /// True if token was deleted otherwise false.
     fn delete_token(&self, id: Uuid) -> impl Future<Output = Result<bool, SQLError>> + Send;
+
+    /// Method that will delete all tokens of a user.
+    ///
+    /// # Errors
+    /// It can return any error related to database connection.
+    ///
+    /// # Returns
+    /// The number of deleted tokens.
+    fn delete_tokens_by_user_id(
+        &self,
+        user_id: i32,
+    ) -> impl Future<Output = Result<u64, SQLError>> + Send;
 }
 
 /// `PostgresTokenRepository` is implementation of `TaskRepository` with postgres
@@ -39,15 +97,88 @@ impl PostgresTokenRepository {
 }
 
 impl TokenRepository for PostgresTokenRepository {
-    async fn add_token(&self, id: Uuid, exp: NaiveDateTime, user_id: i32) -> Result<(), SQLError> {
-        query("INSERT INTO tokens (id, exp, user_id) VALUES ($1, $2, $3)")
+    async fn add_token(
+        &self,
+        id: Uuid,
+        family_id: Uuid,
+        exp: NaiveDateTime,
+        user_id: i32,
+        client: &ClientInfo,
+    ) -> Result<(), SQLError> {
+        query(
+            "INSERT INTO tokens (id, family_id, exp, user_id, created_at, last_used_at, user_agent, ip) \
+            VALUES ($1, $2, $3, $4, COALESCE((SELECT MIN(created_at) FROM tokens WHERE family_id = $2), NOW()), NOW(), $5, $6)",
+        )
+        .bind(id)
+        .bind(family_id)
+        .bind(exp)
+        .bind(user_id)
+        .bind(&client.user_agent)
+        .bind(client.ip.map(|ip| ip.to_string()))
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn use_token(&self, id: Uuid) -> Result<TokenUse, SQLError> {
+        let row = query(
+            "UPDATE tokens SET used = TRUE WHERE id = $1 AND used = FALSE RETURNING family_id",
+        )
+        .bind(id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        if let Some(row) = row {
+            return Ok(TokenUse::Rotated {
+                family_id: row.try_get(0)?,
+            });
+        }
+
+        let row = query("SELECT family_id FROM tokens WHERE id = $1")
             .bind(id)
-            .bind(exp)
+            .fetch_optional(&self.db)
+            .await?;
+
+        match row {
+            Some(row) => Ok(TokenUse::Reused {
+                family_id: row.try_get(0)?,
+            }),
+            None => Ok(TokenUse::NotFound),
+        }
+    }
+
+    async fn delete_token_family(&self, family_id: Uuid, user_id: i32) -> Result<u64, SQLError> {
+        let result = query("DELETE FROM tokens WHERE family_id = $1 AND user_id = $2")
+            .bind(family_id)
             .bind(user_id)
             .execute(&self.db)
             .await?;
 
-        Ok(())
+        Ok(result.rows_affected())
+    }
+
+    async fn get_sessions_by_user_id(&self, user_id: i32) -> Result<Vec<Session>, SQLError> {
+        let rows = query(
+            "SELECT family_id, created_at, last_used_at, user_agent, ip FROM tokens \
+            WHERE user_id = $1 AND used = FALSE AND exp > NOW() ORDER BY last_used_at DESC",
+        )
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        let mut result = Vec::with_capacity(rows.len());
+
+        for row in rows {
+            let id: Uuid = row.try_get(0)?;
+            let created_at: DateTime<Utc> = row.try_get(1)?;
+            let last_used_at: DateTime<Utc> = row.try_get(2)?;
+            let user_agent: Option<String> = row.try_get(3)?;
+            let ip: Option<String> = row.try_get(4)?;
+            result.push(Session::new(id, created_at, last_used_at, user_agent, ip));
+        }
+
+        Ok(result)
     }
 
     async fn delete_token(&self, id: Uuid) -> Result<bool, SQLError> {
@@ -58,4 +189,13 @@ impl TokenRepository for PostgresTokenRepository {
 
         Ok(result.rows_affected() > 0)
     }
+
+    async fn delete_tokens_by_user_id(&self, user_id: i32) -> Result<u64, SQLError> {
+        let result = query("DELETE FROM tokens WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&self.db)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
 }
@@ -1,11 +1,14 @@
+use crate::models::one_time_token::TokenPurpose;
 use crate::models::user;
 use crate::models::user::User;
+use sqlx::postgres::PgRow;
 use sqlx::{query, Error as SQLError, PgPool, Row};
 use std::future::Future;
 
 /// `UserRepository` manages user data.
 pub trait UserRepository: Send + Sync + Clone + 'static {
     /// `check_user_email_username` check if the user email and username are unique
+    /// among the users other than the one with `except_id`.
     /// # Error
     /// It can return any error related to database connection.
     ///
@@ -15,15 +18,19 @@ pub trait UserRepository: Send + Sync + Clone + 'static {
         &self,
         email: &str,
         username: &str,
+        except_id: Option<i32>,
     ) -> impl Future<Output = Result<bool, sqlx::Error>> + Send;
 
     /// `add_user` adds the user.
     /// # Error
     /// It can return any error related to database connection.
+    ///
+    /// # Returns
+    /// The id of the added user.
     fn add_user(
         &self,
         user: &user::UserPayload,
-    ) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
+    ) -> impl Future<Output = Result<i32, sqlx::Error>> + Send;
 
     /// `get_user_by_email` will fetch user with specified email.
     ///
@@ -36,6 +43,154 @@ pub trait UserRepository: Send + Sync + Clone + 'static {
         &self,
         email: &str,
     ) -> impl Future<Output = Result<User, sqlx::Error>> + Send;
+
+    /// `get_user_by_username` will fetch user with specified username.
+    ///
+    /// # Error
+    /// It can return any error related to database connection.
+    ///
+    /// # Returns
+    /// `Ok(User)` If the user is found.
+    fn get_user_by_username(
+        &self,
+        username: &str,
+    ) -> impl Future<Output = Result<User, sqlx::Error>> + Send;
+
+    /// `get_user_by_id` will fetch user with specified id.
+    ///
+    /// # Error
+    /// It can return any error related to database connection.
+    ///
+    /// # Returns
+    /// `Ok(User)` If the user is found.
+    fn get_user_by_id(&self, id: i32) -> impl Future<Output = Result<User, sqlx::Error>> + Send;
+
+    /// `update_password` will set the password hash of the user.
+    ///
+    /// # Error
+    /// It can return any error related to database connection.
+    fn update_password(
+        &self,
+        id: i32,
+        password: &str,
+    ) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
+
+    /// `reset_password` will use the password reset token with `token_hash`, set the password hash
+    /// of its user and delete their refresh tokens, in one transaction.
+    ///
+    /// # Error
+    /// It can return any error related to database connection.
+    ///
+    /// # Returns
+    /// The id of the user, `None` if the token is invalid, expired or already used.
+    fn reset_password(
+        &self,
+        token_hash: &str,
+        password: &str,
+    ) -> impl Future<Output = Result<Option<i32>, sqlx::Error>> + Send;
+
+    /// `update_profile` will set the email and the username of the user.
+    /// The email is marked as not verified if it changed.
+    ///
+    /// # Error
+    /// It can return any error related to database connection.
+    fn update_profile(
+        &self,
+        id: i32,
+        email: &str,
+        username: &str,
+    ) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
+
+    /// `delete_user` will delete the user.
+    /// Their tokens, tasks, preferences, recovery codes and personal access tokens are deleted
+    /// with them by the foreign keys. Their login failures and lockout events, recorded under
+    /// `login_key`, are deleted in the same transaction.
+    ///
+    /// # Error
+    /// It can return any error related to database connection.
+    fn delete_user(
+        &self,
+        id: i32,
+        login_key: &str,
+    ) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
+
+    /// `set_email_verified` will mark the email of the user as verified.
+    ///
+    /// # Error
+    /// It can return any error related to database connection.
+    fn set_email_verified(&self, id: i32) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
+
+    /// `set_totp_secret` will set the TOTP secret of the user that is setting up two-factor authentication.
+    /// The two-factor authentication stays disabled until `enable_totp` is called.
+    ///
+    /// # Error
+    /// It can return any error related to database connection.
+    fn set_totp_secret(
+        &self,
+        id: i32,
+        secret: &str,
+    ) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
+
+    /// `enable_totp` will enable two-factor authentication of the user
+    /// and replace their recovery codes with `recovery_code_hashes`.
+    ///
+    /// # Error
+    /// It can return any error related to database connection.
+    fn enable_totp(
+        &self,
+        id: i32,
+        recovery_code_hashes: &[String],
+    ) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
+
+    /// `disable_totp` will disable two-factor authentication of the user and delete their recovery codes.
+    ///
+    /// # Error
+    /// It can return any error related to database connection.
+    fn disable_totp(&self, id: i32) -> impl Future<Output = Result<(), sqlx::Error>> + Send;
+
+    /// `use_totp_step` will record that a TOTP code of `step` was used,
+    /// so codes of the same or an earlier step can't be used again.
+    ///
+    /// # Error
+    /// It can return any error related to database connection.
+    ///
+    /// # Returns
+    /// `false` if a code of the same or a later step was already used.
+    fn use_totp_step(
+        &self,
+        id: i32,
+        step: i64,
+    ) -> impl Future<Output = Result<bool, sqlx::Error>> + Send;
+
+    /// `use_recovery_code` will mark unused recovery code of the user with `hash` as used.
+    ///
+    /// # Error
+    /// It can return any error related to database connection.
+    ///
+    /// # Returns
+    /// `false` if there is no such unused recovery code.
+    fn use_recovery_code(
+        &self,
+        id: i32,
+        hash: &str,
+    ) -> impl Future<Output = Result<bool, sqlx::Error>> + Send;
+}
+
+/// Columns of `users` read by `user_from_row`.
+const USER_COLUMNS: &str =
+    "id, email, username, password, email_verified, totp_secret, totp_enabled";
+
+/// Function that will read `User` from row with `USER_COLUMNS`.
+fn user_from_row(row: &PgRow) -> Result<User, SQLError> {
+    Ok(User::new(
+        row.try_get(0)?,
+        row.try_get(1)?,
+        row.try_get(2)?,
+        row.try_get(3)?,
+        row.try_get(4)?,
+        row.try_get(5)?,
+        row.try_get(6)?,
+    ))
 }
 
 /// `PostgresUserRepository` is implementation of `UserRepository` with postgres
@@ -55,39 +210,230 @@ impl UserRepository for PostgresUserRepository {
         &self,
         email: &str,
         username: &str,
+        except_id: Option<i32>,
     ) -> Result<bool, SQLError> {
-        let result = sqlx::query("SELECT COUNT(*) FROM users WHERE email = $1 OR username = $2")
-            .bind(&email)
-            .bind(&username)
-            .fetch_one(&self.db)
-            .await?;
+        let result = sqlx::query(
+            "SELECT COUNT(*) FROM users \
+             WHERE (email = $1 OR username = $2) AND id IS DISTINCT FROM $3",
+        )
+        .bind(email)
+        .bind(username)
+        .bind(except_id)
+        .fetch_one(&self.db)
+        .await?;
 
         let count: i64 = result.try_get(0)?;
         Ok(count == 0)
     }
 
-    async fn add_user(&self, user: &user::UserPayload) -> Result<(), SQLError> {
-        query("INSERT INTO users(email, username, password) VALUES ($1, $2, $3)")
-            .bind(&user.email)
-            .bind(&user.username)
-            .bind(&user.password)
+    async fn add_user(&self, user: &user::UserPayload) -> Result<i32, SQLError> {
+        let result =
+            query("INSERT INTO users(email, username, password) VALUES ($1, $2, $3) RETURNING id")
+                .bind(&user.email)
+                .bind(&user.username)
+                .bind(&user.password)
+                .fetch_one(&self.db)
+                .await?;
+
+        result.try_get(0)
+    }
+
+    async fn get_user_by_email(&self, email: &str) -> Result<User, SQLError> {
+        let result = query(&format!(
+            "SELECT {} FROM users WHERE email = $1",
+            USER_COLUMNS
+        ))
+        .bind(email)
+        .fetch_one(&self.db)
+        .await?;
+
+        user_from_row(&result)
+    }
+
+    async fn get_user_by_username(&self, username: &str) -> Result<User, SQLError> {
+        let result = query(&format!(
+            "SELECT {} FROM users WHERE username = $1",
+            USER_COLUMNS
+        ))
+        .bind(username)
+        .fetch_one(&self.db)
+        .await?;
+
+        user_from_row(&result)
+    }
+
+    async fn get_user_by_id(&self, id: i32) -> Result<User, SQLError> {
+        let result = query(&format!("SELECT {} FROM users WHERE id = $1", USER_COLUMNS))
+            .bind(id)
+            .fetch_one(&self.db)
+            .await?;
+
+        user_from_row(&result)
+    }
+
+    async fn update_password(&self, id: i32, password: &str) -> Result<(), SQLError> {
+        query("UPDATE users SET password = $1 WHERE id = $2")
+            .bind(password)
+            .bind(id)
             .execute(&self.db)
             .await?;
 
         Ok(())
     }
 
-    async fn get_user_by_email(&self, email: &str) -> Result<User, SQLError> {
-        let result = query("SELECT id, email, username, password FROM USERS WHERE email = $1")
-            .bind(&email)
-            .fetch_one(&self.db)
+    async fn reset_password(
+        &self,
+        token_hash: &str,
+        password: &str,
+    ) -> Result<Option<i32>, SQLError> {
+        let mut tx = self.db.begin().await?;
+
+        let Some(row) = query(
+            "UPDATE one_time_tokens SET used_at = NOW() \
+             WHERE hash = $1 AND purpose = $2 AND used_at IS NULL AND expires_at > NOW() \
+             RETURNING user_id",
+        )
+        .bind(token_hash)
+        .bind(TokenPurpose::PasswordReset.as_str())
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Ok(None);
+        };
+        let id: i32 = row.try_get(0)?;
+
+        query("UPDATE users SET password = $1 WHERE id = $2")
+            .bind(password)
+            .bind(id)
+            .execute(&mut *tx)
             .await?;
 
-        let id: i32 = result.try_get(0)?;
-        let email: String = result.try_get(1)?;
-        let username: String = result.try_get(2)?;
-        let password: String = result.try_get(3)?;
+        query("DELETE FROM tokens WHERE user_id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(Some(id))
+    }
+
+    async fn update_profile(&self, id: i32, email: &str, username: &str) -> Result<(), SQLError> {
+        query(
+            "UPDATE users SET email = $2, username = $3, \
+             email_verified = email_verified AND email = $2 WHERE id = $1",
+        )
+        .bind(id)
+        .bind(email)
+        .bind(username)
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_user(&self, id: i32, login_key: &str) -> Result<(), SQLError> {
+        let mut tx = self.db.begin().await?;
+
+        query("DELETE FROM users WHERE id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        query("DELETE FROM login_failures WHERE key = $1")
+            .bind(login_key)
+            .execute(&mut *tx)
+            .await?;
+
+        query("DELETE FROM lockout_events WHERE key = $1")
+            .bind(login_key)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await
+    }
+
+    async fn set_email_verified(&self, id: i32) -> Result<(), SQLError> {
+        query("UPDATE users SET email_verified = TRUE WHERE id = $1")
+            .bind(id)
+            .execute(&self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn set_totp_secret(&self, id: i32, secret: &str) -> Result<(), SQLError> {
+        query("UPDATE users SET totp_secret = $1, totp_enabled = FALSE, totp_last_step = NULL WHERE id = $2")
+            .bind(secret)
+            .bind(id)
+            .execute(&self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn enable_totp(&self, id: i32, recovery_code_hashes: &[String]) -> Result<(), SQLError> {
+        let mut tx = self.db.begin().await?;
+
+        query("UPDATE users SET totp_enabled = TRUE WHERE id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        query("DELETE FROM recovery_codes WHERE user_id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        for hash in recovery_code_hashes {
+            query("INSERT INTO recovery_codes(user_id, hash) VALUES ($1, $2)")
+                .bind(id)
+                .bind(hash)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await
+    }
+
+    async fn disable_totp(&self, id: i32) -> Result<(), SQLError> {
+        let mut tx = self.db.begin().await?;
+
+        query("UPDATE users SET totp_secret = NULL, totp_enabled = FALSE, totp_last_step = NULL WHERE id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        query("DELETE FROM recovery_codes WHERE user_id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await
+    }
+
+    async fn use_totp_step(&self, id: i32, step: i64) -> Result<bool, SQLError> {
+        let result = query(
+            "UPDATE users SET totp_last_step = $1 \
+             WHERE id = $2 AND (totp_last_step IS NULL OR totp_last_step < $1)",
+        )
+        .bind(step)
+        .bind(id)
+        .execute(&self.db)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn use_recovery_code(&self, id: i32, hash: &str) -> Result<bool, SQLError> {
+        let result = query(
+            "UPDATE recovery_codes SET used_at = NOW() \
+             WHERE id = (SELECT id FROM recovery_codes WHERE user_id = $1 AND hash = $2 AND used_at IS NULL LIMIT 1)",
+        )
+        .bind(id)
+        .bind(hash)
+        .execute(&self.db)
+        .await?;
 
-        Ok(User::new(id, email, username, password))
+        Ok(result.rows_affected() == 1)
     }
 }
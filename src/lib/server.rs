@@ -1,26 +1,40 @@
-use crate::auth::{access_token_claims, refresh_token_claims, Authenticator};
+use crate::auth::{
+    access_token_claims, refresh_token_claims, require_scope, Authenticator,
+    PersonalAccessTokenVerifier, Scope,
+};
 use crate::handlers;
+use crate::rate_limit::{rate_limit, RateLimitSettings, RateLimiter};
 use crate::services::task::TaskService;
 use crate::services::user::UserService;
 use axum::extract::FromRef;
 use axum::middleware::from_fn_with_state;
 use axum::routing::{delete, get, post, put};
 use axum::Router;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::net::TcpListener;
 
 /// `ServerConfig` holds server configuration.
 pub struct ServerConfig<'a> {
     server_addr: &'a str,
+    /// Authenticator verifying the tokens, it carries the `TokenSettings` from `Config`.
+    /// The user service issues tokens with the same instance, so the issuer, audience
+    /// and lifetimes can't differ between issuing and verifying.
     authenticator: Arc<Authenticator>,
+    rate_limits: RateLimitSettings,
 }
 
 impl<'a> ServerConfig<'a> {
     /// `new` method used to create new server configuration.
-    pub fn new(server_addr: &'a str, authenticator: Arc<Authenticator>) -> Self {
+    pub fn new(
+        server_addr: &'a str,
+        authenticator: Arc<Authenticator>,
+        rate_limits: RateLimitSettings,
+    ) -> Self {
         ServerConfig {
             server_addr,
             authenticator,
+            rate_limits,
         }
     }
 }
@@ -59,11 +73,13 @@ where
 
 /// AuthState is substate of `AppState` for authentication.
 #[derive(Clone)]
-pub struct AuthState {
+pub struct AuthState<V: PersonalAccessTokenVerifier> {
     pub authenticator: Arc<Authenticator>,
+    /// Verifier of the personal access tokens.
+    pub verifier: Arc<V>,
 }
 
-impl<U, T> FromRef<AppState<U, T>> for AuthState
+impl<U, T> FromRef<AppState<U, T>> for AuthState<U>
 where
     U: UserService,
     T: TaskService,
@@ -71,6 +87,7 @@ where
     fn from_ref(state: &AppState<U, T>) -> Self {
         Self {
             authenticator: state.authenticator.clone(),
+            verifier: state.user_service.clone(),
         }
     }
 }
@@ -134,8 +151,12 @@ impl Server {
             server_config.authenticator,
         );
 
+        let auth_limiter = RateLimiter::new(server_config.rate_limits.auth);
+        let task_limiter = RateLimiter::new(server_config.rate_limits.tasks);
+
         let tcp_listener = TcpListener::bind(server_config.server_addr).await?;
         let router = Router::new()
+            .route("/.well-known/jwks.json", get(handlers::auth::jwks::<U>))
             .nest(
                 "/api/v1",
                 Router::new()
@@ -143,23 +164,97 @@ impl Server {
                         "/users",
                         Router::new()
                             .route("/register", post(handlers::user::register))
+                            .route("/policy", get(handlers::user::get_validation_policy))
                             .route("/login", post(handlers::user::login))
+                            .route("/login/2fa", post(handlers::user::login_two_factor))
+                            .route("/password/forgot", post(handlers::user::forgot_password))
+                            .route("/password/reset", post(handlers::user::reset_password))
+                            .route("/verify-email", get(handlers::user::verify_email))
+                            .route(
+                                "/verify-email/resend",
+                                post(handlers::user::resend_verification),
+                            )
                             .route(
                                 "/refresh",
                                 get(handlers::user::refresh).layer(from_fn_with_state(
                                     app_state.clone(),
-                                    refresh_token_claims,
+                                    refresh_token_claims::<U>,
+                                )),
+                            )
+                            .route(
+                                "/logout",
+                                post(handlers::user::logout).layer(from_fn_with_state(
+                                    app_state.clone(),
+                                    refresh_token_claims::<U>,
                                 )),
-                            ),
+                            )
+                            .merge(
+                                Router::new()
+                                    .route(
+                                        "/me",
+                                        get(handlers::user::get_current_user)
+                                            .patch(handlers::user::update_current_user)
+                                            .delete(handlers::user::delete_current_user),
+                                    )
+                                    .route(
+                                        "/me/export",
+                                        get(handlers::user::export_current_user::<U, T>).layer(
+                                            from_fn_with_state(Scope::TasksRead, require_scope),
+                                        ),
+                                    )
+                                    .route(
+                                        "/preferences",
+                                        get(handlers::user::get_preferences)
+                                            .patch(handlers::user::update_preferences),
+                                    )
+                                    .route("/logout-all", post(handlers::user::logout_all))
+                                    .route("/sessions", get(handlers::user::get_sessions))
+                                    .route("/sessions/{id}", delete(handlers::user::revoke_session))
+                                    .route("/password", put(handlers::user::change_password))
+                                    .route("/2fa/setup", post(handlers::user::setup_two_factor))
+                                    .route("/2fa/enable", post(handlers::user::enable_two_factor))
+                                    .route("/2fa/disable", post(handlers::user::disable_two_factor))
+                                    .route(
+                                        "/tokens",
+                                        post(handlers::user::create_personal_access_token)
+                                            .get(handlers::user::get_personal_access_tokens),
+                                    )
+                                    .route(
+                                        "/tokens/{id}",
+                                        delete(handlers::user::revoke_personal_access_token),
+                                    )
+                                    .layer(from_fn_with_state(Scope::AccountManage, require_scope))
+                                    .layer(from_fn_with_state(
+                                        app_state.clone(),
+                                        access_token_claims::<U>,
+                                    )),
+                            )
+                            .layer(from_fn_with_state(auth_limiter, rate_limit)),
                     )
                     .nest(
                         "/tasks",
                         Router::new()
-                            .route("/add", post(handlers::task::add_task))
-                            .route("/get", get(handlers::task::get_tasks))
-                            .route("/update", put(handlers::task::update_task))
-                            .route("/delete/{id}", delete(handlers::task::delete_task))
-                            .layer(from_fn_with_state(app_state.clone(), access_token_claims)),
+                            .merge(
+                                Router::new()
+                                    .route("/get", get(handlers::task::get_tasks))
+                                    .route("/{id}", get(handlers::task::get_task))
+                                    .layer(from_fn_with_state(Scope::TasksRead, require_scope)),
+                            )
+                            .merge(
+                                Router::new()
+                                    .route("/add", post(handlers::task::add_task))
+                                    .route("/update", put(handlers::task::update_task))
+                                    .route("/{id}/status", put(handlers::task::change_task_status))
+                                    .route("/{id}/complete", post(handlers::task::complete_task))
+                                    .route("/{id}/reopen", post(handlers::task::reopen_task))
+                                    .route("/delete/{id}", delete(handlers::task::delete_task))
+                                    .layer(from_fn_with_state(Scope::TasksWrite, require_scope)),
+                            )
+                            .layer(from_fn_with_state(task_limiter, rate_limit))
+                            .layer(from_fn_with_state(
+                                app_state.clone(),
+                                access_token_claims::<U>,
+                            )),
                     ),
             )
             .with_state(app_state);
@@ -172,7 +267,12 @@ impl Server {
 
     /// `run` will run the server.
     pub async fn run(self) -> Result<(), std::io::Error> {
-        axum::serve(self.listener, self.router).await?;
+        axum::serve(
+            self.listener,
+            self.router
+                .into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await?;
         Ok(())
     }
 }
@@ -1,8 +1,10 @@
 use crate::auth::AccessClaims;
-use crate::models::task::{Task, TaskPayload};
+use crate::models::task::{Task, TaskDay, TaskPage, TaskPayload, TaskQuery, TaskStatus};
+use crate::repositories::preferences::PreferencesRepository;
 use crate::repositories::task::TaskRepository;
 use crate::utils::api_error_response::{APIErrorResponse, APIResult};
 use axum::http::StatusCode;
+use chrono::Utc;
 use std::future::Future;
 use std::sync::Arc;
 use uuid::Uuid;
@@ -10,6 +12,7 @@ use uuid::Uuid;
 /// Service used to manage task business logic.
 pub trait TaskService: Send + Sync + Clone + 'static {
     /// Method that will add a new task.
+    /// Tasks without priority get the default priority of the user.
     /// # Returns
     /// The newly created task.
     fn add_task(
@@ -18,32 +21,110 @@ pub trait TaskService: Send + Sync + Clone + 'static {
         claims: AccessClaims,
     ) -> impl Future<Output = APIResult<Task>> + Send;
 
-    /// Method that will fetch all task of a user.
+    /// Method that will fetch a page of tasks of a user.
+    /// The day in `query` is the day in the time zone of the user.
     /// # Returns
-    /// Vector will all tasks.
-    fn get_task(&self, claims: AccessClaims) -> impl Future<Output = APIResult<Vec<Task>>> + Send;
+    /// Page with the tasks matching `query`.
+    fn get_task(
+        &self,
+        claims: AccessClaims,
+        query: &TaskQuery,
+    ) -> impl Future<Output = APIResult<TaskPage>> + Send;
 
-    /// Method that will update an existing task.
-    fn update_task(&self, task: &Task) -> impl Future<Output = APIResult<()>> + Send;
+    /// Method that will fetch all tasks of a user, used to export their data.
+    fn get_all_tasks(
+        &self,
+        claims: AccessClaims,
+    ) -> impl Future<Output = APIResult<Vec<Task>>> + Send;
 
-    /// Method that will delete task by id.
-    fn delete_task(&self, id: Uuid) -> impl Future<Output = APIResult<()>> + Send;
+    /// Method that will fetch a single task of a user.
+    /// # Errors
+    /// `NOT_FOUND` if the task doesn't exist or isn't owned by the user.
+    fn get_task_by_id(
+        &self,
+        id: Uuid,
+        claims: AccessClaims,
+    ) -> impl Future<Output = APIResult<Task>> + Send;
+
+    /// Method that will update an existing task of a user.
+    /// # Errors
+    /// `NOT_FOUND` if the task doesn't exist or isn't owned by the user.
+    fn update_task(
+        &self,
+        task: &Task,
+        claims: AccessClaims,
+    ) -> impl Future<Output = APIResult<()>> + Send;
+
+    /// Method that will move a task of a user to a new status.
+    /// `completed_at` is set when the task is done and cleared otherwise.
+    /// # Errors
+    /// `NOT_FOUND` if the task doesn't exist or isn't owned by the user.
+    /// `CONFLICT` if the task cannot move from its current status to `status`.
+    /// # Returns
+    /// The updated task.
+    fn change_task_status(
+        &self,
+        id: Uuid,
+        status: TaskStatus,
+        claims: AccessClaims,
+    ) -> impl Future<Output = APIResult<Task>> + Send;
+
+    /// Method that will delete task of a user by id.
+    /// # Errors
+    /// `NOT_FOUND` if the task doesn't exist or isn't owned by the user.
+    fn delete_task(
+        &self,
+        id: Uuid,
+        claims: AccessClaims,
+    ) -> impl Future<Output = APIResult<()>> + Send;
 }
 
 #[derive(Clone)]
-pub struct DefaultTaskService<T: TaskRepository> {
+pub struct DefaultTaskService<T: TaskRepository, P: PreferencesRepository> {
     repository: Arc<T>,
+    preferences_repository: Arc<P>,
 }
 
-impl<T: TaskRepository> DefaultTaskService<T> {
-    pub fn new(repository: Arc<T>) -> Self {
-        Self { repository }
+impl<T: TaskRepository, P: PreferencesRepository> DefaultTaskService<T, P> {
+    pub fn new(repository: Arc<T>, preferences_repository: Arc<P>) -> Self {
+        Self {
+            repository,
+            preferences_repository,
+        }
+    }
+
+    /// Method that will replace the day of the query with the bounds of the day
+    /// in the time zone of the user.
+    async fn resolve_day(&self, user_id: i32, query: &TaskQuery) -> APIResult<TaskQuery> {
+        let mut query = query.clone();
+        if let Some(day) = query.day.take() {
+            let preferences = self.preferences_repository.get_preferences(user_id).await?;
+            let day = match day {
+                TaskDay::Today => preferences.today(Utc::now()),
+                TaskDay::Date(date) => date,
+            };
+
+            let (from, to) = preferences.day_bounds(day);
+            query.from = Some(from);
+            query.to = Some(to);
+        }
+
+        Ok(query)
     }
 }
 
-impl<T: TaskRepository> TaskService for DefaultTaskService<T> {
+impl<T: TaskRepository, P: PreferencesRepository> TaskService for DefaultTaskService<T, P> {
     async fn add_task(&self, task: &TaskPayload, claims: AccessClaims) -> APIResult<Task> {
-        let result = self.repository.check_priority(&task.priority).await?;
+        let priority = if task.priority.is_empty() {
+            self.preferences_repository
+                .get_preferences(claims.sub)
+                .await?
+                .default_priority
+        } else {
+            task.priority.clone()
+        };
+
+        let result = self.repository.check_priority(&priority).await?;
         if !result {
             return Err(APIErrorResponse::new(
                 StatusCode::BAD_REQUEST,
@@ -56,8 +137,10 @@ impl<T: TaskRepository> TaskService for DefaultTaskService<T> {
             id,
             task.name.clone(),
             task.description.clone(),
-            task.priority.clone(),
+            priority,
             task.date,
+            TaskStatus::Todo,
+            None,
         );
 
         self.repository.add_task(&task, claims.sub).await?;
@@ -65,12 +148,51 @@ impl<T: TaskRepository> TaskService for DefaultTaskService<T> {
         Ok(task)
     }
 
-    async fn get_task(&self, claims: AccessClaims) -> APIResult<Vec<Task>> {
-        let tasks = self.repository.get_tasks_by_user_id(claims.sub).await?;
+    async fn get_task(&self, claims: AccessClaims, query: &TaskQuery) -> APIResult<TaskPage> {
+        let query = &self.resolve_day(claims.sub, query).await?;
+        let limit = query.limit();
+        // One extra task is fetched to find out if there is a next page.
+        let mut tasks = self
+            .repository
+            .get_tasks_by_user_id(claims.sub, query, limit + 1)
+            .await?;
+
+        let next_cursor = if tasks.len() as i64 > limit {
+            tasks.truncate(limit as usize);
+            tasks.last().map(|task| task.id)
+        } else {
+            None
+        };
+
+        let total = self
+            .repository
+            .count_tasks_by_user_id(claims.sub, query)
+            .await?;
+
+        Ok(TaskPage::new(tasks, next_cursor, total))
+    }
+
+    async fn get_all_tasks(&self, claims: AccessClaims) -> APIResult<Vec<Task>> {
+        let tasks = self
+            .repository
+            .get_tasks_by_user_id(claims.sub, &TaskQuery::default(), i64::MAX)
+            .await?;
         Ok(tasks)
     }
 
-    async fn update_task(&self, task: &Task) -> APIResult<()> {
+    async fn get_task_by_id(&self, id: Uuid, claims: AccessClaims) -> APIResult<Task> {
+        self.repository
+            .get_task_by_id(id, claims.sub)
+            .await
+            .map_err(|err| match err {
+                sqlx::Error::RowNotFound => {
+                    APIErrorResponse::new(StatusCode::NOT_FOUND, String::from("Task not found"))
+                }
+                _ => APIErrorResponse::from(err),
+            })
+    }
+
+    async fn update_task(&self, task: &Task, claims: AccessClaims) -> APIResult<()> {
         if !self.repository.check_priority(&task.priority).await? {
             return Err(APIErrorResponse::new(
                 StatusCode::BAD_REQUEST,
@@ -78,7 +200,7 @@ impl<T: TaskRepository> TaskService for DefaultTaskService<T> {
             ));
         }
 
-        if !self.repository.update_task(&task).await? {
+        if !self.repository.update_task(task, claims.sub).await? {
             return Err(APIErrorResponse::new(
                 StatusCode::NOT_FOUND,
                 String::from("Task not found"),
@@ -88,8 +210,45 @@ impl<T: TaskRepository> TaskService for DefaultTaskService<T> {
         Ok(())
     }
 
-    async fn delete_task(&self, id: Uuid) -> APIResult<()> {
-        if self.repository.delete_task_by_id(id).await? {
+    async fn change_task_status(
+        &self,
+        id: Uuid,
+        status: TaskStatus,
+        claims: AccessClaims,
+    ) -> APIResult<Task> {
+        let mut task = self.get_task_by_id(id, claims.clone()).await?;
+
+        if !task.status.can_transition_to(status) {
+            return Err(APIErrorResponse::new(
+                StatusCode::CONFLICT,
+                format!("Task cannot move from {} to {}", task.status, status),
+            ));
+        }
+
+        let completed_at = if status == TaskStatus::Done {
+            Some(Utc::now())
+        } else {
+            None
+        };
+
+        if !self
+            .repository
+            .update_task_status(id, claims.sub, task.status, status, completed_at)
+            .await?
+        {
+            return Err(APIErrorResponse::new(
+                StatusCode::CONFLICT,
+                String::from("Task was modified concurrently"),
+            ));
+        }
+
+        task.status = status;
+        task.completed_at = completed_at;
+        Ok(task)
+    }
+
+    async fn delete_task(&self, id: Uuid, claims: AccessClaims) -> APIResult<()> {
+        if self.repository.delete_task_by_id(id, claims.sub).await? {
             return Ok(());
         }
 
@@ -99,3 +258,421 @@ impl<T: TaskRepository> TaskService for DefaultTaskService<T> {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::preferences::Preferences;
+    use crate::models::task::{SortOrder, TaskSort};
+    use chrono::{DateTime, Duration};
+    use sqlx::Error as SQLXError;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    const PRIORITIES: [&str; 4] = ["Low", "Medium", "High", "Vital"];
+
+    /// Task repository keeping tasks in memory, used for testing.
+    #[derive(Clone, Default)]
+    struct InMemoryTaskRepository {
+        tasks: Arc<Mutex<Vec<(Task, i32)>>>,
+    }
+
+    impl TaskRepository for InMemoryTaskRepository {
+        async fn add_task(&self, task: &Task, user_id: i32) -> Result<(), SQLXError> {
+            self.tasks.lock().unwrap().push((task.clone(), user_id));
+            Ok(())
+        }
+
+        async fn check_priority(&self, priority: &str) -> Result<bool, SQLXError> {
+            Ok(PRIORITIES.contains(&priority))
+        }
+
+        async fn get_tasks_by_user_id(
+            &self,
+            user_id: i32,
+            filter: &TaskQuery,
+            limit: i64,
+        ) -> Result<Vec<Task>, SQLXError> {
+            let mut tasks = self.filtered(user_id, filter);
+            tasks.sort_by(|a, b| {
+                let ordering = match filter.sort {
+                    TaskSort::Date => a.date.cmp(&b.date),
+                    TaskSort::Priority => rank(&a.priority).cmp(&rank(&b.priority)),
+                    TaskSort::Name => a.name.cmp(&b.name),
+                };
+                ordering.then(a.id.cmp(&b.id))
+            });
+            if filter.order == SortOrder::Desc {
+                tasks.reverse();
+            }
+
+            let start = match filter.cursor {
+                Some(cursor) => match tasks.iter().position(|task| task.id == cursor) {
+                    Some(position) => position + 1,
+                    None => tasks.len(),
+                },
+                None => 0,
+            };
+
+            Ok(tasks.into_iter().skip(start).take(limit as usize).collect())
+        }
+
+        async fn count_tasks_by_user_id(
+            &self,
+            user_id: i32,
+            filter: &TaskQuery,
+        ) -> Result<i64, SQLXError> {
+            Ok(self.filtered(user_id, filter).len() as i64)
+        }
+
+        async fn get_task_by_id(&self, id: Uuid, user_id: i32) -> Result<Task, SQLXError> {
+            self.tasks
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|(task, owner)| task.id == id && *owner == user_id)
+                .map(|(task, _)| task.clone())
+                .ok_or(SQLXError::RowNotFound)
+        }
+
+        async fn update_task(&self, task: &Task, user_id: i32) -> Result<bool, SQLXError> {
+            let mut tasks = self.tasks.lock().unwrap();
+            match tasks
+                .iter_mut()
+                .find(|(stored, owner)| stored.id == task.id && *owner == user_id)
+            {
+                Some((stored, _)) => {
+                    stored.name = task.name.clone();
+                    stored.description = task.description.clone();
+                    stored.priority = task.priority.clone();
+                    stored.date = task.date;
+                    Ok(true)
+                }
+                None => Ok(false),
+            }
+        }
+
+        async fn update_task_status(
+            &self,
+            id: Uuid,
+            user_id: i32,
+            from: TaskStatus,
+            to: TaskStatus,
+            completed_at: Option<DateTime<Utc>>,
+        ) -> Result<bool, SQLXError> {
+            let mut tasks = self.tasks.lock().unwrap();
+            match tasks.iter_mut().find(|(stored, owner)| {
+                stored.id == id && *owner == user_id && stored.status == from
+            }) {
+                Some((stored, _)) => {
+                    stored.status = to;
+                    stored.completed_at = completed_at;
+                    Ok(true)
+                }
+                None => Ok(false),
+            }
+        }
+
+        async fn delete_task_by_id(&self, id: Uuid, user_id: i32) -> Result<bool, SQLXError> {
+            let mut tasks = self.tasks.lock().unwrap();
+            let len = tasks.len();
+            tasks.retain(|(task, owner)| !(task.id == id && *owner == user_id));
+            Ok(tasks.len() != len)
+        }
+    }
+
+    impl InMemoryTaskRepository {
+        fn filtered(&self, user_id: i32, filter: &TaskQuery) -> Vec<Task> {
+            self.tasks
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(task, owner)| {
+                    *owner == user_id
+                        && filter.priority.as_ref().is_none_or(|p| *p == task.priority)
+                        && filter.from.is_none_or(|from| task.date >= from)
+                        && filter.to.is_none_or(|to| task.date <= to)
+                })
+                .map(|(task, _)| task.clone())
+                .collect()
+        }
+    }
+
+    /// Preferences repository keeping preferences in memory, used for testing.
+    #[derive(Clone, Default)]
+    struct InMemoryPreferencesRepository {
+        preferences: Arc<Mutex<HashMap<i32, Preferences>>>,
+    }
+
+    impl PreferencesRepository for InMemoryPreferencesRepository {
+        async fn get_preferences(&self, user_id: i32) -> Result<Preferences, SQLXError> {
+            Ok(self
+                .preferences
+                .lock()
+                .unwrap()
+                .get(&user_id)
+                .cloned()
+                .unwrap_or_default())
+        }
+
+        async fn set_preferences(
+            &self,
+            user_id: i32,
+            preferences: &Preferences,
+        ) -> Result<bool, SQLXError> {
+            self.preferences
+                .lock()
+                .unwrap()
+                .insert(user_id, preferences.clone());
+            Ok(true)
+        }
+    }
+
+    type TestService = DefaultTaskService<InMemoryTaskRepository, InMemoryPreferencesRepository>;
+
+    fn service() -> TestService {
+        DefaultTaskService::new(
+            Arc::new(InMemoryTaskRepository::default()),
+            Arc::new(InMemoryPreferencesRepository::default()),
+        )
+    }
+
+    fn rank(priority: &str) -> usize {
+        PRIORITIES.iter().position(|p| *p == priority).unwrap_or(0)
+    }
+
+    fn claims(sub: i32) -> AccessClaims {
+        AccessClaims::new(sub, 0, 0, String::new(), String::new(), vec![])
+    }
+
+    async fn service_with_task() -> (TestService, Task) {
+        let service = service();
+        let payload = TaskPayload::new(
+            String::from("Name"),
+            String::from("Description"),
+            String::from("Low"),
+            Utc::now(),
+        );
+        let task = service.add_task(&payload, claims(1)).await.unwrap();
+        (service, task)
+    }
+
+    #[tokio::test]
+    async fn test_get_task_by_id_of_other_user() {
+        let (service, task) = service_with_task().await;
+
+        assert_eq!(
+            service
+                .get_task_by_id(task.id, claims(2))
+                .await
+                .unwrap_err(),
+            APIErrorResponse::new(StatusCode::NOT_FOUND, String::from("Task not found"))
+        );
+        assert_eq!(
+            service.get_task_by_id(task.id, claims(1)).await.unwrap().id,
+            task.id
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_task_of_other_user() {
+        let (service, mut task) = service_with_task().await;
+        task.name = String::from("Changed");
+
+        assert_eq!(
+            service.update_task(&task, claims(2)).await,
+            Err(APIErrorResponse::new(
+                StatusCode::NOT_FOUND,
+                String::from("Task not found"),
+            ))
+        );
+        let page = service
+            .get_task(claims(1), &TaskQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(page.tasks[0].name, "Name");
+
+        assert_eq!(service.update_task(&task, claims(1)).await, Ok(()));
+        let page = service
+            .get_task(claims(1), &TaskQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(page.tasks[0].name, "Changed");
+    }
+
+    #[tokio::test]
+    async fn test_change_task_status() {
+        let (service, task) = service_with_task().await;
+
+        assert_eq!(
+            service
+                .change_task_status(task.id, TaskStatus::Done, claims(2))
+                .await
+                .unwrap_err(),
+            APIErrorResponse::new(StatusCode::NOT_FOUND, String::from("Task not found"))
+        );
+
+        let done = service
+            .change_task_status(task.id, TaskStatus::Done, claims(1))
+            .await
+            .unwrap();
+        assert_eq!(done.status, TaskStatus::Done);
+        assert!(done.completed_at.is_some());
+
+        assert_eq!(
+            service
+                .change_task_status(task.id, TaskStatus::InProgress, claims(1))
+                .await
+                .unwrap_err(),
+            APIErrorResponse::new(
+                StatusCode::CONFLICT,
+                String::from("Task cannot move from done to in-progress"),
+            )
+        );
+
+        let reopened = service
+            .change_task_status(task.id, TaskStatus::Todo, claims(1))
+            .await
+            .unwrap();
+        assert_eq!(reopened.status, TaskStatus::Todo);
+        assert_eq!(reopened.completed_at, None);
+
+        let stored = service.get_task_by_id(task.id, claims(1)).await.unwrap();
+        assert_eq!(stored.status, TaskStatus::Todo);
+        assert_eq!(stored.completed_at, None);
+    }
+
+    #[tokio::test]
+    async fn test_delete_task_of_other_user() {
+        let (service, task) = service_with_task().await;
+
+        assert_eq!(
+            service.delete_task(task.id, claims(2)).await,
+            Err(APIErrorResponse::new(
+                StatusCode::NOT_FOUND,
+                String::from("Task not found"),
+            ))
+        );
+        assert_eq!(
+            service
+                .get_task(claims(1), &TaskQuery::default())
+                .await
+                .unwrap()
+                .total,
+            1
+        );
+
+        assert_eq!(service.delete_task(task.id, claims(1)).await, Ok(()));
+        assert!(service
+            .get_task(claims(1), &TaskQuery::default())
+            .await
+            .unwrap()
+            .tasks
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_preferences() {
+        let service = service();
+        service
+            .preferences_repository
+            .set_preferences(
+                1,
+                &Preferences {
+                    timezone: String::from("America/New_York"),
+                    default_priority: String::from("High"),
+                    ..Preferences::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        for (name, date) in [
+            ("Late", "2025-03-15T03:30:00Z"),
+            ("Evening", "2025-03-16T01:00:00Z"),
+            ("Next", "2025-03-16T05:00:00Z"),
+        ] {
+            let payload = TaskPayload::new(
+                String::from(name),
+                String::from("Description"),
+                String::new(),
+                date.parse().unwrap(),
+            );
+            let task = service.add_task(&payload, claims(1)).await.unwrap();
+            assert_eq!(task.priority, "High");
+        }
+
+        // The 15th of March in New York is from 04:00 UTC to 04:00 UTC of the next day.
+        let query = TaskQuery {
+            day: Some(TaskDay::Date("2025-03-15".parse().unwrap())),
+            ..Default::default()
+        };
+        let page = service.get_task(claims(1), &query).await.unwrap();
+        let names: Vec<&str> = page.tasks.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["Evening"]);
+        assert_eq!(page.total, 1);
+
+        // Other users use the default preferences.
+        let payload = TaskPayload::new(
+            String::from("Name"),
+            String::from("Description"),
+            String::new(),
+            Utc::now(),
+        );
+        let task = service.add_task(&payload, claims(2)).await.unwrap();
+        assert_eq!(task.priority, "Medium");
+        let query = TaskQuery {
+            day: Some(TaskDay::Today),
+            ..Default::default()
+        };
+        assert_eq!(service.get_task(claims(2), &query).await.unwrap().total, 1);
+    }
+
+    #[tokio::test]
+    async fn test_get_task_pages() {
+        let service = service();
+        let now = Utc::now();
+        for (i, priority) in PRIORITIES.iter().enumerate() {
+            let payload = TaskPayload::new(
+                format!("Task {}", i),
+                String::from("Description"),
+                priority.to_string(),
+                now + Duration::days(i as i64),
+            );
+            service.add_task(&payload, claims(1)).await.unwrap();
+        }
+
+        let mut query = TaskQuery {
+            limit: Some(3),
+            sort: TaskSort::Priority,
+            order: SortOrder::Desc,
+            ..Default::default()
+        };
+        let page = service.get_task(claims(1), &query).await.unwrap();
+        let priorities: Vec<&str> = page.tasks.iter().map(|t| t.priority.as_str()).collect();
+        assert_eq!(priorities, vec!["Vital", "High", "Medium"]);
+        assert_eq!(page.total, 4);
+        assert!(page.next_cursor.is_some());
+
+        query.cursor = page.next_cursor;
+        let page = service.get_task(claims(1), &query).await.unwrap();
+        assert_eq!(page.tasks.len(), 1);
+        assert_eq!(page.tasks[0].priority, "Low");
+        assert_eq!(page.next_cursor, None);
+
+        let query = TaskQuery {
+            from: Some(now + Duration::days(1)),
+            to: Some(now + Duration::days(2)),
+            ..Default::default()
+        };
+        let page = service.get_task(claims(1), &query).await.unwrap();
+        let names: Vec<&str> = page.tasks.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["Task 1", "Task 2"]);
+        assert_eq!(page.total, 2);
+        assert_eq!(page.next_cursor, None);
+
+        // Exports aren't paginated.
+        assert_eq!(service.get_all_tasks(claims(1)).await.unwrap().len(), 4);
+        assert!(service.get_all_tasks(claims(2)).await.unwrap().is_empty());
+    }
+}
@@ -1,10 +1,30 @@
 use crate::auth;
-use crate::auth::Authenticator;
+use crate::auth::{Authenticator, PersonalAccessTokenVerifier};
+use crate::mail::{Mail, Mailer};
+use crate::models::one_time_token::{OneTimeToken, TokenPurpose};
+use crate::models::personal_access_token::{
+    CreatedPersonalAccessToken, PersonalAccessToken, PersonalAccessTokenPayload,
+};
+use crate::models::policy::ValidationPolicy;
+use crate::models::preferences::{Preferences, PreferencesPayload};
+use crate::models::session::{ClientInfo, Session};
 use crate::models::token_group::TokenGroup;
-use crate::models::user::UserPayload;
-use crate::repositories::token::TokenRepository;
+use crate::models::two_factor::{
+    normalize_recovery_code, LoginResponse, RecoveryCodes, TwoFactorChallenge,
+    TwoFactorLoginPayload, TwoFactorSetup,
+};
+use crate::models::user::{
+    LoginPayload, PasswordChangePayload, PasswordResetPayload, User, UserPayload, UserUpdatePayload,
+};
+use crate::repositories::login_attempt::LoginAttemptRepository;
+use crate::repositories::one_time_token::OneTimeTokenRepository;
+use crate::repositories::personal_access_token::PersonalAccessTokenRepository;
+use crate::repositories::preferences::PreferencesRepository;
+use crate::repositories::token::{TokenRepository, TokenUse};
 use crate::repositories::user::UserRepository;
 use crate::utils::api_error_response::{APIErrorResponse, APIResult};
+use crate::utils::password::PasswordHasher;
+use crate::utils::totp;
 use axum::http::StatusCode;
 use chrono::{Duration, Utc};
 use std::future::Future;
@@ -13,65 +33,397 @@ use std::sync::Arc;
 use uuid::Uuid;
 
 /// Service used to manage user business logic.
-pub trait UserService: Send + Sync + Clone + 'static {
+/// It also verifies the personal access tokens of the users.
+pub trait UserService: PersonalAccessTokenVerifier + Send + Sync + Clone + 'static {
+    /// Method returning the rules of the usernames and passwords the payloads are validated with.
+    fn validation_policy(&self) -> &ValidationPolicy;
+
     /// Method used to register the user.
+    /// A mail with an email verification token is sent to the user.
     fn register(
         &self,
         user: &mut UserPayload,
     ) -> impl Future<Output = APIResult<StatusCode>> + Send;
 
-    /// Method used to log in the user.
-    fn login(&self, user: &UserPayload) -> impl Future<Output = APIResult<TokenGroup>> + Send;
+    /// Method used to log in the user from the client described by `client`.
+    /// If the user has two-factor authentication enabled a challenge token is returned instead of the tokens,
+    /// and the login is finished with `login_two_factor`.
+    /// # Errors
+    /// `FORBIDDEN` if verified email is required and the email of the user isn't verified.
+    /// `TOO_MANY_REQUESTS` if the account or the client is locked after too many failed attempts.
+    fn login(
+        &self,
+        payload: &LoginPayload,
+        client: &ClientInfo,
+    ) -> impl Future<Output = APIResult<LoginResponse>> + Send;
 
-    /// Method used to register the user.
+    /// Method used to finish the login of user with two-factor authentication
+    /// with TOTP code or unused recovery code.
+    /// # Errors
+    /// `UNAUTHORIZED` if the challenge token or the code is invalid.
+    /// `TOO_MANY_REQUESTS` if the account or the client is locked after too many failed attempts.
+    fn login_two_factor(
+        &self,
+        payload: &TwoFactorLoginPayload,
+        client: &ClientInfo,
+    ) -> impl Future<Output = APIResult<TokenGroup>> + Send;
+
+    /// Method used to refresh the tokens of the user from the client described by `client`.
     fn refresh(
         &self,
         claims: auth::RefreshClaims,
+        client: &ClientInfo,
+    ) -> impl Future<Output = APIResult<TokenGroup>> + Send;
+
+    /// Method used to log out the user by revoking the token family of the presented refresh token.
+    fn logout(&self, claims: auth::RefreshClaims) -> impl Future<Output = APIResult<()>> + Send;
+
+    /// Method used to log out the user from all devices by revoking all of their refresh tokens.
+    fn logout_all(&self, claims: auth::AccessClaims) -> impl Future<Output = APIResult<()>> + Send;
+
+    /// Method used to fetch the profile of the user.
+    fn get_current_user(
+        &self,
+        claims: auth::AccessClaims,
+    ) -> impl Future<Output = APIResult<User>> + Send;
+
+    /// Method used to change the email or the username of the user.
+    /// A changed email has to be verified again, so a verification mail is sent to it.
+    /// # Errors
+    /// `CONFLICT` if the email or the username is used by another user.
+    fn update_current_user(
+        &self,
+        payload: &UserUpdatePayload,
+        claims: auth::AccessClaims,
+    ) -> impl Future<Output = APIResult<User>> + Send;
+
+    /// Method used to delete the account of the user with all their data.
+    /// # Errors
+    /// `FORBIDDEN` if the password is wrong.
+    fn delete_current_user(
+        &self,
+        password: &str,
+        claims: auth::AccessClaims,
+    ) -> impl Future<Output = APIResult<()>> + Send;
+
+    /// Method used to fetch the preferences of the user.
+    fn get_preferences(
+        &self,
+        claims: auth::AccessClaims,
+    ) -> impl Future<Output = APIResult<Preferences>> + Send;
+
+    /// Method used to change the preferences of the user.
+    /// # Errors
+    /// `BAD_REQUEST` if the default priority doesn't exist.
+    fn update_preferences(
+        &self,
+        payload: &PreferencesPayload,
+        claims: auth::AccessClaims,
+    ) -> impl Future<Output = APIResult<Preferences>> + Send;
+
+    /// Method used to list the active sessions of the user.
+    fn get_sessions(
+        &self,
+        claims: auth::AccessClaims,
+    ) -> impl Future<Output = APIResult<Vec<Session>>> + Send;
+
+    /// Method used to revoke a session of the user.
+    /// # Errors
+    /// `NOT_FOUND` if the session doesn't exist or isn't owned by the user.
+    fn revoke_session(
+        &self,
+        id: Uuid,
+        claims: auth::AccessClaims,
+    ) -> impl Future<Output = APIResult<()>> + Send;
+
+    /// Method used to change the password of the user.
+    /// All refresh tokens of the user are revoked and new tokens are issued for the client
+    /// described by `client`, so only the caller stays logged in.
+    /// # Errors
+    /// `FORBIDDEN` if the current password is wrong.
+    fn change_password(
+        &self,
+        payload: &PasswordChangePayload,
+        claims: auth::AccessClaims,
+        client: &ClientInfo,
     ) -> impl Future<Output = APIResult<TokenGroup>> + Send;
+
+    /// Method used to send a password reset token to the user with `email`.
+    /// Nothing is sent if there is no such user, but the result is the same
+    /// so that it can't be used to find out which emails are registered.
+    /// The mail is sent in the background, failures are only logged.
+    fn request_password_reset(&self, email: &str) -> impl Future<Output = APIResult<()>> + Send;
+
+    /// Method used to set a new password with a password reset token.
+    /// All refresh tokens of the user are revoked.
+    /// # Errors
+    /// `BAD_REQUEST` if the token is invalid, expired or already used.
+    fn reset_password(
+        &self,
+        payload: &PasswordResetPayload,
+    ) -> impl Future<Output = APIResult<()>> + Send;
+
+    /// Method used to verify the email of the user with an email verification token.
+    /// # Errors
+    /// `BAD_REQUEST` if the token is invalid, expired or already used.
+    fn verify_email(&self, token: &str) -> impl Future<Output = APIResult<()>> + Send;
+
+    /// Method used to send a new email verification token to the user with `email`.
+    /// Nothing is sent if there is no such user or the email is already verified,
+    /// but the result is the same so that it can't be used to find out which emails are registered.
+    /// The mail is sent in the background, failures are only logged.
+    fn resend_verification(&self, email: &str) -> impl Future<Output = APIResult<()>> + Send;
+
+    /// Method used to start setting up two-factor authentication with new TOTP secret.
+    /// # Errors
+    /// `CONFLICT` if two-factor authentication is already enabled.
+    fn setup_two_factor(
+        &self,
+        claims: auth::AccessClaims,
+    ) -> impl Future<Output = APIResult<TwoFactorSetup>> + Send;
+
+    /// Method used to enable two-factor authentication with the first code from the authenticator app.
+    /// # Errors
+    /// `CONFLICT` if two-factor authentication is already enabled or isn't set up.
+    /// `BAD_REQUEST` if the code is invalid.
+    ///
+    /// # Returns
+    /// The recovery codes that can be used once each in place of TOTP code.
+    fn enable_two_factor(
+        &self,
+        code: &str,
+        claims: auth::AccessClaims,
+    ) -> impl Future<Output = APIResult<RecoveryCodes>> + Send;
+
+    /// Method used to disable two-factor authentication.
+    /// # Errors
+    /// `FORBIDDEN` if the password is wrong.
+    fn disable_two_factor(
+        &self,
+        password: &str,
+        claims: auth::AccessClaims,
+    ) -> impl Future<Output = APIResult<()>> + Send;
+
+    /// Method used to create personal access token.
+    /// The token gets the scopes of the caller when none are requested.
+    /// # Errors
+    /// `FORBIDDEN` if the caller wasn't granted a requested scope.
+    ///
+    /// # Returns
+    /// The token, which is shown only once.
+    fn create_personal_access_token(
+        &self,
+        payload: &PersonalAccessTokenPayload,
+        claims: auth::AccessClaims,
+    ) -> impl Future<Output = APIResult<CreatedPersonalAccessToken>> + Send;
+
+    /// Method used to get the personal access tokens of the user.
+    fn get_personal_access_tokens(
+        &self,
+        claims: auth::AccessClaims,
+    ) -> impl Future<Output = APIResult<Vec<PersonalAccessToken>>> + Send;
+
+    /// Method used to revoke personal access token.
+    /// # Errors
+    /// `NOT_FOUND` if the user has no token with the id.
+    fn revoke_personal_access_token(
+        &self,
+        id: Uuid,
+        claims: auth::AccessClaims,
+    ) -> impl Future<Output = APIResult<()>> + Send;
+}
+
+/// Lifetime of the challenge tokens returned by login for users with two-factor authentication in minutes.
+const TWO_FACTOR_CHALLENGE_TTL_MINUTES: i64 = 5;
+
+/// Struct holding the settings of the user accounts.
+#[derive(Debug, Clone)]
+pub struct UserSettings {
+    /// Lifetime of the password reset tokens.
+    pub password_reset_ttl: Duration,
+    /// Url of the page where the users set the new password.
+    /// The reset token is appended as `token` query parameter.
+    /// When not set the mail contains only the token.
+    pub password_reset_url: Option<String>,
+    /// Lifetime of the email verification tokens.
+    pub email_verification_ttl: Duration,
+    /// Url used to verify the email, the verification token is appended as `token` query parameter.
+    /// When not set the mail contains only the token.
+    pub email_verification_url: Option<String>,
+    /// When set users can't log in until their email is verified.
+    pub require_verified_email: bool,
+    /// Issuer shown in authenticator apps next to the TOTP codes.
+    pub totp_issuer: String,
+    /// Settings of the lockout after failed logins.
+    pub lockout: LockoutSettings,
+    /// Hasher of the passwords.
+    pub password_hasher: PasswordHasher,
+    /// Rules of the usernames and passwords.
+    pub validation_policy: ValidationPolicy,
+}
+
+impl Default for UserSettings {
+    fn default() -> Self {
+        Self {
+            password_reset_ttl: Duration::minutes(30),
+            password_reset_url: None,
+            email_verification_ttl: Duration::days(1),
+            email_verification_url: None,
+            require_verified_email: false,
+            totp_issuer: String::from("Tasks"),
+            lockout: LockoutSettings::default(),
+            password_hasher: PasswordHasher::default(),
+            validation_policy: ValidationPolicy::default(),
+        }
+    }
+}
+
+/// Struct holding the settings of the lockout after failed logins,
+/// which protects the accounts from guessing passwords and codes.
+#[derive(Debug, Clone)]
+pub struct LockoutSettings {
+    /// Number of failed logins of an account after which the account is locked.
+    pub max_failures: i32,
+    /// Number of failed logins from an IP after which the IP is locked.
+    pub max_failures_per_ip: i32,
+    /// Duration of the first lockout, each further failure doubles it.
+    pub lockout: Duration,
+    /// Longest duration of a lockout.
+    pub max_lockout: Duration,
+    /// Failures are forgotten when there was no failure for this duration.
+    pub failure_window: Duration,
+}
+
+impl LockoutSettings {
+    /// Method that will return the lockout after `failures` when the limit is `max_failures`.
+    pub fn lockout_after(&self, failures: i32, max_failures: i32) -> Option<Duration> {
+        if failures < max_failures {
+            return None;
+        }
+
+        let doublings = (failures - max_failures).min(20) as u32;
+        Some((self.lockout * 2_i32.pow(doublings)).min(self.max_lockout))
+    }
+}
+
+impl Default for LockoutSettings {
+    fn default() -> Self {
+        Self {
+            max_failures: 5,
+            max_failures_per_ip: 20,
+            lockout: Duration::minutes(1),
+            max_lockout: Duration::hours(1),
+            failure_window: Duration::hours(1),
+        }
+    }
+}
+
+/// Function that will return the key of the login attempts of the account with `email`.
+fn account_login_key(email: &str) -> String {
+    let email: String = email.trim().to_lowercase().chars().take(255).collect();
+    format!("account:{}", email)
 }
 
 /// Service that is default implementation of `UserService`.
 #[derive(Clone)]
-pub struct DefaultUserService<U, T>
+pub struct DefaultUserService<U, T, O, P, L, R, M>
 where
     U: UserRepository,
     T: TokenRepository,
+    O: OneTimeTokenRepository,
+    P: PersonalAccessTokenRepository,
+    L: LoginAttemptRepository,
+    R: PreferencesRepository,
+    M: Mailer,
 {
     user_repository: Arc<U>,
     token_repository: Arc<T>,
+    one_time_token_repository: Arc<O>,
+    personal_access_token_repository: Arc<P>,
+    login_attempt_repository: Arc<L>,
+    preferences_repository: Arc<R>,
+    mailer: M,
     authenticator: Arc<Authenticator>,
+    settings: UserSettings,
+    /// Hash checked when logging in as unknown user, so it takes as long as a wrong password.
+    dummy_password_hash: String,
 }
 
-impl<U, T> DefaultUserService<U, T>
+impl<U, T, O, P, L, R, M> DefaultUserService<U, T, O, P, L, R, M>
 where
     U: UserRepository,
     T: TokenRepository,
+    O: OneTimeTokenRepository,
+    P: PersonalAccessTokenRepository,
+    L: LoginAttemptRepository,
+    R: PreferencesRepository,
+    M: Mailer,
 {
+    /// `new` method used to create the service. Fails if the password hasher can't hash,
+    /// since logins of unknown users are verified against a dummy hash to take as long
+    /// as the ones of known users.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         user_repository: Arc<U>,
         token_repository: Arc<T>,
+        one_time_token_repository: Arc<O>,
+        personal_access_token_repository: Arc<P>,
+        login_attempt_repository: Arc<L>,
+        preferences_repository: Arc<R>,
+        mailer: M,
         authenticator: Arc<Authenticator>,
-    ) -> Self {
-        Self {
+        settings: UserSettings,
+    ) -> APIResult<Self> {
+        let dummy_password_hash = settings.password_hasher.hash(&Uuid::new_v4().to_string())?;
+
+        Ok(Self {
             user_repository,
             token_repository,
+            one_time_token_repository,
+            personal_access_token_repository,
+            login_attempt_repository,
+            preferences_repository,
+            mailer,
             authenticator,
+            settings,
+            dummy_password_hash,
+        })
+    }
+
+    /// Method that will fetch the user by email or by username, depending on the login identifier.
+    async fn get_user_by_login(&self, payload: &LoginPayload) -> Result<User, sqlx::Error> {
+        if payload.is_email() {
+            self.user_repository
+                .get_user_by_email(&payload.identifier)
+                .await
+        } else {
+            self.user_repository
+                .get_user_by_username(&payload.identifier)
+                .await
         }
     }
 
-    async fn create_token_group(&self, user_id: i32) -> APIResult<TokenGroup> {
+    /// Method that will create new access and refresh tokens.
+    /// The refresh token is added to the token family with `family_id`.
+    async fn create_token_group(
+        &self,
+        user_id: i32,
+        family_id: Uuid,
+        client: &ClientInfo,
+    ) -> APIResult<TokenGroup> {
+        let settings = self.authenticator.settings();
         let access_token = self
             .authenticator
             .new_access_token(
                 user_id,
-                Utc::now().add(Duration::minutes(10)).timestamp() as usize,
+                Utc::now().add(settings.access_token_ttl).timestamp() as usize,
             )
             .map_err(|err| {
                 APIErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
             })?;
 
         let refresh_token_id = Uuid::new_v4();
-        let refresh_token_duration = Utc::now() + Duration::days(15);
+        let refresh_token_duration = Utc::now() + settings.refresh_token_ttl;
         let refresh_token = self
             .authenticator
             .new_refresh_token(
@@ -86,24 +438,294 @@ where
         self.token_repository
             .add_token(
                 refresh_token_id,
+                family_id,
                 refresh_token_duration.naive_utc(),
                 user_id,
+                client,
             )
             .await?;
 
         Ok(TokenGroup::new(access_token, refresh_token))
     }
+
+    /// Method that will mark the refresh token as used.
+    /// If the token was already used the whole token family is revoked,
+    /// because either the legitimate user or an attacker holds a stolen token.
+    ///
+    /// # Returns
+    /// The family id of the token.
+    async fn use_refresh_token(&self, claims: &auth::RefreshClaims) -> APIResult<Uuid> {
+        match self.token_repository.use_token(claims.jti).await? {
+            TokenUse::Rotated { family_id } => Ok(family_id),
+            TokenUse::Reused { family_id } => {
+                let revoked = self
+                    .token_repository
+                    .delete_token_family(family_id, claims.sub)
+                    .await?;
+                println!(
+                    "Security event: reuse of refresh token {} of user {}, revoked {} tokens of family {}",
+                    claims.jti, claims.sub, revoked, family_id
+                );
+                Err(APIErrorResponse::new(
+                    StatusCode::UNAUTHORIZED,
+                    String::from("Unauthorized"),
+                ))
+            }
+            TokenUse::NotFound => Err(APIErrorResponse::new(
+                StatusCode::UNAUTHORIZED,
+                String::from("Unauthorized"),
+            )),
+        }
+    }
+
+    /// Method that will create one-time token for `purpose` that expires after `ttl`.
+    /// The previous unused tokens of the user for the same purpose stop working.
+    ///
+    /// # Returns
+    /// The token that should be sent to the user.
+    async fn create_one_time_token(
+        &self,
+        user_id: i32,
+        purpose: TokenPurpose,
+        ttl: Duration,
+    ) -> APIResult<String> {
+        let token = OneTimeToken::generate();
+        self.one_time_token_repository
+            .add_token(&token.hash, user_id, purpose, Utc::now() + ttl)
+            .await?;
+        Ok(token.token)
+    }
+
+    /// Method that will fetch the user of the access token.
+    async fn get_user(&self, claims: &auth::AccessClaims) -> APIResult<User> {
+        self.user_repository
+            .get_user_by_id(claims.sub)
+            .await
+            .map_err(|err| match err {
+                sqlx::Error::RowNotFound => {
+                    APIErrorResponse::new(StatusCode::UNAUTHORIZED, String::from("Unauthorized"))
+                }
+                _ => APIErrorResponse::from(err),
+            })
+    }
+
+    /// Method that will check TOTP code or recovery code of the user.
+    /// TOTP codes and recovery codes can be used only once.
+    async fn verify_two_factor_code(&self, user: &User, code: &str) -> APIResult<bool> {
+        let code = code.trim();
+        if code.len() == 6 && code.chars().all(|c| c.is_ascii_digit()) {
+            let Some(secret) = &user.totp_secret else {
+                return Ok(false);
+            };
+
+            return match totp::verify(secret, code, Utc::now().timestamp() as u64) {
+                Some(step) => Ok(self
+                    .user_repository
+                    .use_totp_step(user.id, step as i64)
+                    .await?),
+                None => Ok(false),
+            };
+        }
+
+        let hash = OneTimeToken::hash(&normalize_recovery_code(code));
+        Ok(self
+            .user_repository
+            .use_recovery_code(user.id, &hash)
+            .await?)
+    }
+
+    /// Method that will return the keys of the login attempts of the account with `email`
+    /// and of the client with their limits.
+    fn login_attempt_keys(&self, email: &str, client: &ClientInfo) -> Vec<(String, i32)> {
+        let mut keys = vec![(account_login_key(email), self.settings.lockout.max_failures)];
+        if let Some(ip) = client.ip {
+            keys.push((
+                format!("ip:{}", ip),
+                self.settings.lockout.max_failures_per_ip,
+            ));
+        }
+        keys
+    }
+
+    /// Method that will reject the login with `TOO_MANY_REQUESTS` if any of the keys is locked.
+    async fn check_lockout(&self, keys: &[(String, i32)]) -> APIResult<()> {
+        let keys: Vec<String> = keys.iter().map(|(key, _)| key.clone()).collect();
+        if let Some(locked_until) = self
+            .login_attempt_repository
+            .get_locked_until(&keys)
+            .await?
+        {
+            let milliseconds = (locked_until - Utc::now()).num_milliseconds().max(0) as u64;
+            return Err(APIErrorResponse::new(
+                StatusCode::TOO_MANY_REQUESTS,
+                String::from("Too many failed login attempts"),
+            )
+            .with_retry_after(milliseconds.div_ceil(1000)));
+        }
+
+        Ok(())
+    }
+
+    /// Method that will record failed login and lock the keys that reached their limit.
+    async fn record_login_failure(&self, keys: &[(String, i32)]) -> APIResult<()> {
+        let settings = &self.settings.lockout;
+        for (key, max_failures) in keys {
+            let failures = self
+                .login_attempt_repository
+                .add_failure(key, Utc::now() - settings.failure_window)
+                .await?;
+
+            if let Some(lockout) = settings.lockout_after(failures, *max_failures) {
+                let locked_until = Utc::now() + lockout;
+                self.login_attempt_repository
+                    .lock(key, failures, locked_until)
+                    .await?;
+                println!(
+                    "Locked {} until {} after {} failed login attempts",
+                    key, locked_until, failures
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Method that will replace the password hash of the user if it was made
+    /// with another algorithm or cost than configured. Failures are only logged.
+    async fn upgrade_password_hash(&self, user: &User, password: &str) {
+        let hasher = &self.settings.password_hasher;
+        if !hasher.needs_rehash(&user.password) {
+            return;
+        }
+
+        let result = match hasher.hash(password) {
+            Ok(hash) => self
+                .user_repository
+                .update_password(user.id, &hash)
+                .await
+                .map_err(APIErrorResponse::from),
+            Err(err) => Err(err),
+        };
+        if let Err(err) = result {
+            println!(
+                "Failed to upgrade password hash of user {}: {:?}",
+                user.id, err
+            );
+        }
+    }
+
+    /// Method that will send a mail with email verification token to the user.
+    async fn send_verification_mail(
+        &self,
+        user_id: i32,
+        email: &str,
+        username: &str,
+    ) -> APIResult<()> {
+        let token = self
+            .create_one_time_token(
+                user_id,
+                TokenPurpose::EmailVerification,
+                self.settings.email_verification_ttl,
+            )
+            .await?;
+
+        let mut body = format!(
+            "Hello {},\n\nUse the token below to verify your email. It expires in {} hours.\n\nToken: {}\n",
+            username,
+            self.settings.email_verification_ttl.num_hours(),
+            token
+        );
+        if let Some(url) = &self.settings.email_verification_url {
+            body.push_str(&format!("\nOr open {}?token={}\n", url, token));
+        }
+
+        self.mailer
+            .send(Mail::new(
+                String::from(email),
+                String::from("Verify your email"),
+                body,
+            ))
+            .await
+            .map_err(|err| {
+                APIErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+            })
+    }
+
+    /// Method that will send a new email verification mail to the user with `email`,
+    /// if there is one and the email isn't verified yet.
+    async fn resend_verification_mail(&self, email: &str) -> APIResult<()> {
+        let user = match self.user_repository.get_user_by_email(email).await {
+            Ok(user) => user,
+            Err(sqlx::Error::RowNotFound) => return Ok(()),
+            Err(err) => return Err(APIErrorResponse::from(err)),
+        };
+
+        if user.email_verified {
+            return Ok(());
+        }
+
+        self.send_verification_mail(user.id, &user.email, &user.username)
+            .await
+    }
+
+    /// Method that will send a mail with password reset token to the user with `email`, if there is one.
+    async fn send_password_reset_mail(&self, email: &str) -> APIResult<()> {
+        let user = match self.user_repository.get_user_by_email(email).await {
+            Ok(user) => user,
+            Err(sqlx::Error::RowNotFound) => return Ok(()),
+            Err(err) => return Err(APIErrorResponse::from(err)),
+        };
+
+        let token = self
+            .create_one_time_token(
+                user.id,
+                TokenPurpose::PasswordReset,
+                self.settings.password_reset_ttl,
+            )
+            .await?;
+
+        let mut body = format!(
+            "Hello {},\n\nUse the token below to reset your password. It expires in {} minutes.\n\nToken: {}\n",
+            user.username,
+            self.settings.password_reset_ttl.num_minutes(),
+            token
+        );
+        if let Some(url) = &self.settings.password_reset_url {
+            body.push_str(&format!("\nOr open {}?token={}\n", url, token));
+        }
+        body.push_str("\nIf you didn't request a password reset, ignore this mail.\n");
+
+        self.mailer
+            .send(Mail::new(
+                user.email,
+                String::from("Reset your password"),
+                body,
+            ))
+            .await
+            .map_err(|err| {
+                APIErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+            })
+    }
 }
 
-impl<U, T> UserService for DefaultUserService<U, T>
+impl<U, T, O, P, L, R, M> UserService for DefaultUserService<U, T, O, P, L, R, M>
 where
     U: UserRepository,
     T: TokenRepository,
+    O: OneTimeTokenRepository,
+    P: PersonalAccessTokenRepository,
+    L: LoginAttemptRepository,
+    R: PreferencesRepository,
+    M: Mailer,
 {
+    fn validation_policy(&self) -> &ValidationPolicy {
+        &self.settings.validation_policy
+    }
+
     async fn register(&self, user: &mut UserPayload) -> APIResult<StatusCode> {
         if !self
             .user_repository
-            .check_user_email_username(&user.email, &user.username)
+            .check_user_email_username(&user.email, &user.username, None)
             .await?
         {
             return Err(APIErrorResponse::new(
@@ -112,47 +734,2216 @@ where
             ));
         }
 
-        user.password = bcrypt::hash(&user.password, bcrypt::DEFAULT_COST).map_err(|err| {
-            APIErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
-        })?;
+        user.password = self.settings.password_hasher.hash(&user.password)?;
 
-        self.user_repository.add_user(&user).await?;
+        let id = self.user_repository.add_user(user).await?;
+        if let Err(err) = self
+            .send_verification_mail(id, &user.email, &user.username)
+            .await
+        {
+            println!("Failed to send verification mail to user {}: {:?}", id, err);
+        }
         Ok(StatusCode::CREATED)
     }
 
-    async fn login(&self, user: &UserPayload) -> APIResult<TokenGroup> {
-        let fetched_user = self
+    async fn login(&self, payload: &LoginPayload, client: &ClientInfo) -> APIResult<LoginResponse> {
+        let fetched_user = match self.get_user_by_login(payload).await {
+            Ok(fetched_user) => Some(fetched_user),
+            Err(sqlx::Error::RowNotFound) => None,
+            Err(err) => return Err(APIErrorResponse::from(err)),
+        };
+
+        // Failures are counted per account, so logging in by email and by username share the limit.
+        let account = fetched_user
+            .as_ref()
+            .map_or(payload.identifier.as_str(), |user| user.email.as_str());
+        let keys = self.login_attempt_keys(account, client);
+        self.check_lockout(&keys).await?;
+
+        let hash = fetched_user
+            .as_ref()
+            .map_or(self.dummy_password_hash.as_str(), |user| {
+                user.password.as_str()
+            });
+        let verified = self
+            .settings
+            .password_hasher
+            .verify(&payload.password, hash);
+        let fetched_user = match fetched_user {
+            Some(fetched_user) if verified => fetched_user,
+            _ => {
+                self.record_login_failure(&keys).await?;
+                return Err(APIErrorResponse::new(
+                    StatusCode::UNAUTHORIZED,
+                    String::from("Invalid login or password"),
+                ));
+            }
+        };
+        self.upgrade_password_hash(&fetched_user, &payload.password)
+            .await;
+
+        if self.settings.require_verified_email && !fetched_user.email_verified {
+            return Err(APIErrorResponse::new(
+                StatusCode::FORBIDDEN,
+                String::from("Email is not verified"),
+            ));
+        }
+
+        if fetched_user.totp_enabled {
+            let challenge_token = self
+                .authenticator
+                .new_challenge_token(
+                    fetched_user.id,
+                    Utc::now()
+                        .add(Duration::minutes(TWO_FACTOR_CHALLENGE_TTL_MINUTES))
+                        .timestamp() as usize,
+                )
+                .map_err(|err| {
+                    APIErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+                })?;
+            return Ok(LoginResponse::Challenge(TwoFactorChallenge::new(
+                challenge_token,
+            )));
+        }
+
+        self.login_attempt_repository
+            .clear_failures(&account_login_key(&fetched_user.email))
+            .await?;
+        let group = self
+            .create_token_group(fetched_user.id, Uuid::new_v4(), client)
+            .await?;
+        Ok(LoginResponse::Tokens(group))
+    }
+
+    async fn login_two_factor(
+        &self,
+        payload: &TwoFactorLoginPayload,
+        client: &ClientInfo,
+    ) -> APIResult<TokenGroup> {
+        let unauthorized =
+            || APIErrorResponse::new(StatusCode::UNAUTHORIZED, String::from("Unauthorized"));
+        let claims = self
+            .authenticator
+            .verify_challenge_token(&payload.challenge_token)
+            .map_err(|_| unauthorized())?;
+
+        let user = self
             .user_repository
-            .get_user_by_email(&user.email)
+            .get_user_by_id(claims.sub)
             .await
             .map_err(|err| match err {
-                sqlx::Error::RowNotFound => APIErrorResponse::new(
-                    StatusCode::UNAUTHORIZED,
-                    String::from("Invalid email or password"),
-                ),
+                sqlx::Error::RowNotFound => unauthorized(),
                 _ => APIErrorResponse::from(err),
             })?;
+        if !user.totp_enabled {
+            return Err(unauthorized());
+        }
 
-        if !bcrypt::verify(&user.password, &fetched_user.password).is_ok() {
+        let keys = self.login_attempt_keys(&user.email, client);
+        self.check_lockout(&keys).await?;
+        if !self.verify_two_factor_code(&user, &payload.code).await? {
+            self.record_login_failure(&keys).await?;
             return Err(APIErrorResponse::new(
                 StatusCode::UNAUTHORIZED,
-                String::from("Invalid email or password"),
+                String::from("Invalid code"),
             ));
         }
 
-        self.create_token_group(fetched_user.id).await
+        self.login_attempt_repository
+            .clear_failures(&keys[0].0)
+            .await?;
+        self.create_token_group(user.id, Uuid::new_v4(), client)
+            .await
+    }
+
+    async fn refresh(
+        &self,
+        claims: auth::RefreshClaims,
+        client: &ClientInfo,
+    ) -> APIResult<TokenGroup> {
+        let family_id = self.use_refresh_token(&claims).await?;
+        self.create_token_group(claims.sub, family_id, client).await
+    }
+
+    async fn logout(&self, claims: auth::RefreshClaims) -> APIResult<()> {
+        let family_id = self.use_refresh_token(&claims).await?;
+        self.token_repository
+            .delete_token_family(family_id, claims.sub)
+            .await?;
+        Ok(())
+    }
+
+    async fn logout_all(&self, claims: auth::AccessClaims) -> APIResult<()> {
+        self.token_repository
+            .delete_tokens_by_user_id(claims.sub)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_current_user(&self, claims: auth::AccessClaims) -> APIResult<User> {
+        self.get_user(&claims).await
     }
 
-    async fn refresh(&self, claims: auth::RefreshClaims) -> APIResult<TokenGroup> {
-        let result = self.token_repository.delete_token(claims.jti).await?;
+    async fn update_current_user(
+        &self,
+        payload: &UserUpdatePayload,
+        claims: auth::AccessClaims,
+    ) -> APIResult<User> {
+        let user = self.get_user(&claims).await?;
+        let email = payload.email.as_deref().unwrap_or(&user.email);
+        let username = payload.username.as_deref().unwrap_or(&user.username);
 
-        if !result {
+        if !self
+            .user_repository
+            .check_user_email_username(email, username, Some(user.id))
+            .await?
+        {
             return Err(APIErrorResponse::new(
-                StatusCode::UNAUTHORIZED,
-                String::from("Unauthorized"),
+                StatusCode::CONFLICT,
+                String::from("Email or username already exists"),
+            ));
+        }
+
+        self.user_repository
+            .update_profile(user.id, email, username)
+            .await?;
+        let updated = self.user_repository.get_user_by_id(user.id).await?;
+
+        if updated.email != user.email {
+            if let Err(err) = self
+                .send_verification_mail(updated.id, &updated.email, &updated.username)
+                .await
+            {
+                println!(
+                    "Failed to send verification mail to user {}: {:?}",
+                    updated.id, err
+                );
+            }
+        }
+        Ok(updated)
+    }
+
+    async fn delete_current_user(
+        &self,
+        password: &str,
+        claims: auth::AccessClaims,
+    ) -> APIResult<()> {
+        let user = self.get_user(&claims).await?;
+        if !self
+            .settings
+            .password_hasher
+            .verify(password, &user.password)
+        {
+            return Err(APIErrorResponse::new(
+                StatusCode::FORBIDDEN,
+                String::from("Invalid password"),
+            ));
+        }
+
+        self.user_repository
+            .delete_user(user.id, &account_login_key(&user.email))
+            .await?;
+        println!("Deleted user {}", user.id);
+        Ok(())
+    }
+
+    async fn get_preferences(&self, claims: auth::AccessClaims) -> APIResult<Preferences> {
+        let user = self.get_user(&claims).await?;
+        let preferences = self.preferences_repository.get_preferences(user.id).await?;
+        Ok(preferences)
+    }
+
+    async fn update_preferences(
+        &self,
+        payload: &PreferencesPayload,
+        claims: auth::AccessClaims,
+    ) -> APIResult<Preferences> {
+        let user = self.get_user(&claims).await?;
+        let preferences =
+            payload.apply(self.preferences_repository.get_preferences(user.id).await?);
+
+        if !self
+            .preferences_repository
+            .set_preferences(user.id, &preferences)
+            .await?
+        {
+            return Err(APIErrorResponse::new(
+                StatusCode::BAD_REQUEST,
+                String::from("Invalid priority"),
+            ));
+        }
+        Ok(preferences)
+    }
+
+    async fn get_sessions(&self, claims: auth::AccessClaims) -> APIResult<Vec<Session>> {
+        let sessions = self
+            .token_repository
+            .get_sessions_by_user_id(claims.sub)
+            .await?;
+        Ok(sessions)
+    }
+
+    async fn revoke_session(&self, id: Uuid, claims: auth::AccessClaims) -> APIResult<()> {
+        if self
+            .token_repository
+            .delete_token_family(id, claims.sub)
+            .await?
+            == 0
+        {
+            return Err(APIErrorResponse::new(
+                StatusCode::NOT_FOUND,
+                String::from("Session not found"),
+            ));
+        }
+
+        Ok(())
+    }
+
+    async fn change_password(
+        &self,
+        payload: &PasswordChangePayload,
+        claims: auth::AccessClaims,
+        client: &ClientInfo,
+    ) -> APIResult<TokenGroup> {
+        let user = self
+            .user_repository
+            .get_user_by_id(claims.sub)
+            .await
+            .map_err(|err| match err {
+                sqlx::Error::RowNotFound => {
+                    APIErrorResponse::new(StatusCode::UNAUTHORIZED, String::from("Unauthorized"))
+                }
+                _ => APIErrorResponse::from(err),
+            })?;
+
+        if !self
+            .settings
+            .password_hasher
+            .verify(&payload.current_password, &user.password)
+        {
+            return Err(APIErrorResponse::new(
+                StatusCode::FORBIDDEN,
+                String::from("Invalid current password"),
             ));
         }
 
-        self.create_token_group(claims.sub).await
+        let password = self.settings.password_hasher.hash(&payload.new_password)?;
+        self.user_repository
+            .update_password(user.id, &password)
+            .await?;
+
+        self.token_repository
+            .delete_tokens_by_user_id(user.id)
+            .await?;
+        self.create_token_group(user.id, Uuid::new_v4(), client)
+            .await
+    }
+
+    async fn request_password_reset(&self, email: &str) -> APIResult<()> {
+        // The mail is sent off the request path, so neither the response nor its timing
+        // shows whether the email is registered.
+        let service = self.clone();
+        let email = String::from(email);
+        tokio::spawn(async move {
+            if let Err(err) = service.send_password_reset_mail(&email).await {
+                println!("Failed to send password reset mail: {:?}", err);
+            }
+        });
+        Ok(())
+    }
+
+    async fn reset_password(&self, payload: &PasswordResetPayload) -> APIResult<()> {
+        // The password is hashed first, so the token is used only together with the new password.
+        let password = self.settings.password_hasher.hash(&payload.new_password)?;
+        self.user_repository
+            .reset_password(&OneTimeToken::hash(&payload.token), &password)
+            .await?
+            .ok_or_else(|| {
+                APIErrorResponse::new(
+                    StatusCode::BAD_REQUEST,
+                    String::from("Invalid or expired token"),
+                )
+            })?;
+        Ok(())
+    }
+
+    async fn verify_email(&self, token: &str) -> APIResult<()> {
+        let user_id = self
+            .one_time_token_repository
+            .use_token(&OneTimeToken::hash(token), TokenPurpose::EmailVerification)
+            .await?
+            .ok_or_else(|| {
+                APIErrorResponse::new(
+                    StatusCode::BAD_REQUEST,
+                    String::from("Invalid or expired token"),
+                )
+            })?;
+
+        self.user_repository.set_email_verified(user_id).await?;
+        Ok(())
+    }
+
+    async fn resend_verification(&self, email: &str) -> APIResult<()> {
+        // Like the password reset mail, sent off the request path.
+        let service = self.clone();
+        let email = String::from(email);
+        tokio::spawn(async move {
+            if let Err(err) = service.resend_verification_mail(&email).await {
+                println!("Failed to resend verification mail: {:?}", err);
+            }
+        });
+        Ok(())
+    }
+
+    async fn setup_two_factor(&self, claims: auth::AccessClaims) -> APIResult<TwoFactorSetup> {
+        let user = self.get_user(&claims).await?;
+        if user.totp_enabled {
+            return Err(APIErrorResponse::new(
+                StatusCode::CONFLICT,
+                String::from("Two-factor authentication is already enabled"),
+            ));
+        }
+
+        let secret = totp::generate_secret();
+        let otpauth_uri = totp::otpauth_uri(&secret, &self.settings.totp_issuer, &user.email)
+            .ok_or_else(|| {
+                APIErrorResponse::new(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    String::from("Failed to create TOTP secret"),
+                )
+            })?;
+        self.user_repository
+            .set_totp_secret(user.id, &secret)
+            .await?;
+
+        Ok(TwoFactorSetup {
+            secret,
+            otpauth_uri,
+        })
+    }
+
+    async fn enable_two_factor(
+        &self,
+        code: &str,
+        claims: auth::AccessClaims,
+    ) -> APIResult<RecoveryCodes> {
+        let user = self.get_user(&claims).await?;
+        if user.totp_enabled {
+            return Err(APIErrorResponse::new(
+                StatusCode::CONFLICT,
+                String::from("Two-factor authentication is already enabled"),
+            ));
+        }
+        if user.totp_secret.is_none() {
+            return Err(APIErrorResponse::new(
+                StatusCode::CONFLICT,
+                String::from("Two-factor authentication isn't set up"),
+            ));
+        }
+
+        let code = code.trim();
+        if code.len() != 6 || !self.verify_two_factor_code(&user, code).await? {
+            return Err(APIErrorResponse::new(
+                StatusCode::BAD_REQUEST,
+                String::from("Invalid code"),
+            ));
+        }
+
+        let codes = RecoveryCodes::generate();
+        let hashes: Vec<String> = codes
+            .recovery_codes
+            .iter()
+            .map(|code| OneTimeToken::hash(&normalize_recovery_code(code)))
+            .collect();
+        self.user_repository.enable_totp(user.id, &hashes).await?;
+
+        Ok(codes)
+    }
+
+    async fn disable_two_factor(
+        &self,
+        password: &str,
+        claims: auth::AccessClaims,
+    ) -> APIResult<()> {
+        let user = self.get_user(&claims).await?;
+        if !self
+            .settings
+            .password_hasher
+            .verify(password, &user.password)
+        {
+            return Err(APIErrorResponse::new(
+                StatusCode::FORBIDDEN,
+                String::from("Invalid password"),
+            ));
+        }
+
+        self.user_repository.disable_totp(user.id).await?;
+        Ok(())
+    }
+
+    async fn create_personal_access_token(
+        &self,
+        payload: &PersonalAccessTokenPayload,
+        claims: auth::AccessClaims,
+    ) -> APIResult<CreatedPersonalAccessToken> {
+        let scopes = payload.scopes.clone().unwrap_or(claims.scopes.clone());
+        if let Some(scope) = scopes.iter().find(|scope| !claims.has_scope(**scope)) {
+            return Err(APIErrorResponse::new(
+                StatusCode::FORBIDDEN,
+                format!("Missing scope {}", scope.as_str()),
+            ));
+        }
+
+        let generated = PersonalAccessToken::generate();
+        let personal_access_token = PersonalAccessToken::new(
+            Uuid::new_v4(),
+            claims.sub,
+            String::from(payload.name.trim()),
+            scopes,
+            Utc::now(),
+            payload.expires_at,
+            None,
+        );
+        self.personal_access_token_repository
+            .add_token(&personal_access_token, &generated.hash)
+            .await?;
+
+        Ok(CreatedPersonalAccessToken {
+            token: generated.token,
+            personal_access_token,
+        })
+    }
+
+    async fn get_personal_access_tokens(
+        &self,
+        claims: auth::AccessClaims,
+    ) -> APIResult<Vec<PersonalAccessToken>> {
+        let tokens = self
+            .personal_access_token_repository
+            .get_tokens_by_user_id(claims.sub)
+            .await?;
+        Ok(tokens)
+    }
+
+    async fn revoke_personal_access_token(
+        &self,
+        id: Uuid,
+        claims: auth::AccessClaims,
+    ) -> APIResult<()> {
+        if !self
+            .personal_access_token_repository
+            .delete_token(id, claims.sub)
+            .await?
+        {
+            return Err(APIErrorResponse::new(
+                StatusCode::NOT_FOUND,
+                String::from("Token not found"),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl<U, T, O, P, L, R, M> PersonalAccessTokenVerifier for DefaultUserService<U, T, O, P, L, R, M>
+where
+    U: UserRepository,
+    T: TokenRepository,
+    O: OneTimeTokenRepository,
+    P: PersonalAccessTokenRepository,
+    L: LoginAttemptRepository,
+    R: PreferencesRepository,
+    M: Mailer,
+{
+    async fn verify_personal_access_token(&self, token: &str) -> APIResult<auth::AccessClaims> {
+        let personal_access_token = self
+            .personal_access_token_repository
+            .use_token(&OneTimeToken::hash(token))
+            .await?
+            .ok_or(APIErrorResponse::new(
+                StatusCode::UNAUTHORIZED,
+                String::from("Unauthorized"),
+            ))?;
+
+        // Tokens that never expire have `exp` of 0.
+        let settings = self.authenticator.settings();
+        Ok(auth::AccessClaims::new(
+            personal_access_token.user_id,
+            personal_access_token
+                .expires_at
+                .map_or(0, |expires_at| expires_at.timestamp() as usize),
+            personal_access_token.created_at.timestamp() as usize,
+            settings.issuer.clone(),
+            settings.audience.clone(),
+            personal_access_token.scopes,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Scope;
+    use crate::models::user::User;
+    use crate::utils::password::PasswordAlgorithm;
+    use chrono::{DateTime, NaiveDateTime};
+    use sqlx::Error as SQLError;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use totp_rs::{Secret, TOTP};
+
+    /// User repository keeping users in memory, used for testing.
+    #[derive(Clone, Default)]
+    struct InMemoryUserRepository {
+        users: Arc<Mutex<Vec<User>>>,
+        /// Last used TOTP step of each user.
+        totp_steps: Arc<Mutex<HashMap<i32, i64>>>,
+        /// Recovery codes as `(user_id, hash, used)`.
+        recovery_codes: Arc<Mutex<Vec<(i32, String, bool)>>>,
+        /// Repositories shared with the service, used by `reset_password` and `delete_user`.
+        one_time_tokens: InMemoryOneTimeTokenRepository,
+        tokens: InMemoryTokenRepository,
+        login_attempts: InMemoryLoginAttemptRepository,
+    }
+
+    impl InMemoryUserRepository {
+        /// Method that will update the user with `id`.
+        fn update(&self, id: i32, update: impl FnOnce(&mut User)) {
+            if let Some(user) = self
+                .users
+                .lock()
+                .unwrap()
+                .iter_mut()
+                .find(|user| user.id == id)
+            {
+                update(user);
+            }
+        }
+    }
+
+    impl UserRepository for InMemoryUserRepository {
+        async fn check_user_email_username(
+            &self,
+            email: &str,
+            username: &str,
+            except_id: Option<i32>,
+        ) -> Result<bool, SQLError> {
+            Ok(!self.users.lock().unwrap().iter().any(|user| {
+                Some(user.id) != except_id && (user.email == email || user.username == username)
+            }))
+        }
+
+        async fn add_user(&self, user: &UserPayload) -> Result<i32, SQLError> {
+            let mut users = self.users.lock().unwrap();
+            let id = users.len() as i32 + 1;
+            users.push(User::new(
+                id,
+                user.email.clone(),
+                user.username.clone(),
+                user.password.clone(),
+                false,
+                None,
+                false,
+            ));
+            Ok(id)
+        }
+
+        async fn get_user_by_email(&self, email: &str) -> Result<User, SQLError> {
+            self.users
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|user| user.email == email)
+                .cloned()
+                .ok_or(SQLError::RowNotFound)
+        }
+
+        async fn get_user_by_username(&self, username: &str) -> Result<User, SQLError> {
+            self.users
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|user| user.username == username)
+                .cloned()
+                .ok_or(SQLError::RowNotFound)
+        }
+
+        async fn get_user_by_id(&self, id: i32) -> Result<User, SQLError> {
+            self.users
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|user| user.id == id)
+                .cloned()
+                .ok_or(SQLError::RowNotFound)
+        }
+
+        async fn update_password(&self, id: i32, password: &str) -> Result<(), SQLError> {
+            if let Some(user) = self
+                .users
+                .lock()
+                .unwrap()
+                .iter_mut()
+                .find(|user| user.id == id)
+            {
+                user.password = String::from(password);
+            }
+            Ok(())
+        }
+
+        async fn reset_password(
+            &self,
+            token_hash: &str,
+            password: &str,
+        ) -> Result<Option<i32>, SQLError> {
+            let Some(id) = self
+                .one_time_tokens
+                .use_token(token_hash, TokenPurpose::PasswordReset)
+                .await?
+            else {
+                return Ok(None);
+            };
+
+            self.update_password(id, password).await?;
+            self.tokens.delete_tokens_by_user_id(id).await?;
+            Ok(Some(id))
+        }
+
+        async fn update_profile(
+            &self,
+            id: i32,
+            email: &str,
+            username: &str,
+        ) -> Result<(), SQLError> {
+            self.update(id, |user| {
+                user.email_verified = user.email_verified && user.email == email;
+                user.email = String::from(email);
+                user.username = String::from(username);
+            });
+            Ok(())
+        }
+
+        async fn delete_user(&self, id: i32, login_key: &str) -> Result<(), SQLError> {
+            self.users.lock().unwrap().retain(|user| user.id != id);
+            self.login_attempts
+                .failures
+                .lock()
+                .unwrap()
+                .remove(login_key);
+            self.login_attempts
+                .events
+                .lock()
+                .unwrap()
+                .retain(|(key, _)| key != login_key);
+            Ok(())
+        }
+
+        async fn set_email_verified(&self, id: i32) -> Result<(), SQLError> {
+            self.update(id, |user| user.email_verified = true);
+            Ok(())
+        }
+
+        async fn set_totp_secret(&self, id: i32, secret: &str) -> Result<(), SQLError> {
+            self.update(id, |user| {
+                user.totp_secret = Some(String::from(secret));
+                user.totp_enabled = false;
+            });
+            self.totp_steps.lock().unwrap().remove(&id);
+            Ok(())
+        }
+
+        async fn enable_totp(
+            &self,
+            id: i32,
+            recovery_code_hashes: &[String],
+        ) -> Result<(), SQLError> {
+            self.update(id, |user| user.totp_enabled = true);
+            let mut codes = self.recovery_codes.lock().unwrap();
+            codes.retain(|(user_id, _, _)| *user_id != id);
+            codes.extend(
+                recovery_code_hashes
+                    .iter()
+                    .map(|hash| (id, hash.clone(), false)),
+            );
+            Ok(())
+        }
+
+        async fn disable_totp(&self, id: i32) -> Result<(), SQLError> {
+            self.update(id, |user| {
+                user.totp_secret = None;
+                user.totp_enabled = false;
+            });
+            self.totp_steps.lock().unwrap().remove(&id);
+            self.recovery_codes
+                .lock()
+                .unwrap()
+                .retain(|(user_id, _, _)| *user_id != id);
+            Ok(())
+        }
+
+        async fn use_totp_step(&self, id: i32, step: i64) -> Result<bool, SQLError> {
+            let mut steps = self.totp_steps.lock().unwrap();
+            match steps.get(&id) {
+                Some(last) if *last >= step => Ok(false),
+                _ => {
+                    steps.insert(id, step);
+                    Ok(true)
+                }
+            }
+        }
+
+        async fn use_recovery_code(&self, id: i32, hash: &str) -> Result<bool, SQLError> {
+            let mut codes = self.recovery_codes.lock().unwrap();
+            match codes
+                .iter_mut()
+                .find(|(user_id, code, used)| *user_id == id && code == hash && !used)
+            {
+                Some(code) => {
+                    code.2 = true;
+                    Ok(true)
+                }
+                None => Ok(false),
+            }
+        }
+    }
+
+    /// Refresh token stored by `InMemoryTokenRepository`.
+    #[derive(Clone)]
+    struct StoredToken {
+        id: Uuid,
+        family_id: Uuid,
+        user_id: i32,
+        used: bool,
+        created_at: DateTime<Utc>,
+        client: ClientInfo,
+    }
+
+    /// Token repository keeping tokens in memory, used for testing.
+    #[derive(Clone, Default)]
+    struct InMemoryTokenRepository {
+        tokens: Arc<Mutex<Vec<StoredToken>>>,
+    }
+
+    impl InMemoryTokenRepository {
+        /// Method returning the number of unused tokens of a user.
+        fn count(&self, user_id: i32) -> usize {
+            self.tokens
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|token| token.user_id == user_id && !token.used)
+                .count()
+        }
+    }
+
+    impl TokenRepository for InMemoryTokenRepository {
+        async fn add_token(
+            &self,
+            id: Uuid,
+            family_id: Uuid,
+            _exp: NaiveDateTime,
+            user_id: i32,
+            client: &ClientInfo,
+        ) -> Result<(), SQLError> {
+            let mut tokens = self.tokens.lock().unwrap();
+            let created_at = tokens
+                .iter()
+                .filter(|token| token.family_id == family_id)
+                .map(|token| token.created_at)
+                .min()
+                .unwrap_or_else(Utc::now);
+            tokens.push(StoredToken {
+                id,
+                family_id,
+                user_id,
+                used: false,
+                created_at,
+                client: client.clone(),
+            });
+            Ok(())
+        }
+
+        async fn use_token(&self, id: Uuid) -> Result<TokenUse, SQLError> {
+            let mut tokens = self.tokens.lock().unwrap();
+            match tokens.iter_mut().find(|token| token.id == id) {
+                Some(token) if token.used => Ok(TokenUse::Reused {
+                    family_id: token.family_id,
+                }),
+                Some(token) => {
+                    token.used = true;
+                    Ok(TokenUse::Rotated {
+                        family_id: token.family_id,
+                    })
+                }
+                None => Ok(TokenUse::NotFound),
+            }
+        }
+
+        async fn delete_token_family(
+            &self,
+            family_id: Uuid,
+            user_id: i32,
+        ) -> Result<u64, SQLError> {
+            let mut tokens = self.tokens.lock().unwrap();
+            let len = tokens.len();
+            tokens.retain(|token| !(token.family_id == family_id && token.user_id == user_id));
+            Ok((len - tokens.len()) as u64)
+        }
+
+        async fn get_sessions_by_user_id(&self, user_id: i32) -> Result<Vec<Session>, SQLError> {
+            Ok(self
+                .tokens
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|token| token.user_id == user_id && !token.used)
+                .map(|token| {
+                    Session::new(
+                        token.family_id,
+                        token.created_at,
+                        Utc::now(),
+                        token.client.user_agent.clone(),
+                        token.client.ip.map(|ip| ip.to_string()),
+                    )
+                })
+                .collect())
+        }
+
+        async fn delete_token(&self, id: Uuid) -> Result<bool, SQLError> {
+            let mut tokens = self.tokens.lock().unwrap();
+            let len = tokens.len();
+            tokens.retain(|token| token.id != id);
+            Ok(tokens.len() != len)
+        }
+
+        async fn delete_tokens_by_user_id(&self, user_id: i32) -> Result<u64, SQLError> {
+            let mut tokens = self.tokens.lock().unwrap();
+            let len = tokens.len();
+            tokens.retain(|token| token.user_id != user_id);
+            Ok((len - tokens.len()) as u64)
+        }
+    }
+
+    /// One-time token stored by `InMemoryOneTimeTokenRepository`.
+    struct StoredOneTimeToken {
+        hash: String,
+        user_id: i32,
+        purpose: TokenPurpose,
+        expires_at: DateTime<Utc>,
+        used: bool,
+    }
+
+    /// One-time token repository keeping tokens in memory, used for testing.
+    #[derive(Clone, Default)]
+    struct InMemoryOneTimeTokenRepository {
+        tokens: Arc<Mutex<Vec<StoredOneTimeToken>>>,
+    }
+
+    impl OneTimeTokenRepository for InMemoryOneTimeTokenRepository {
+        async fn add_token(
+            &self,
+            hash: &str,
+            user_id: i32,
+            purpose: TokenPurpose,
+            expires_at: DateTime<Utc>,
+        ) -> Result<(), SQLError> {
+            let mut tokens = self.tokens.lock().unwrap();
+            tokens.retain(|token| {
+                !(token.user_id == user_id && token.purpose == purpose && !token.used)
+            });
+            tokens.push(StoredOneTimeToken {
+                hash: String::from(hash),
+                user_id,
+                purpose,
+                expires_at,
+                used: false,
+            });
+            Ok(())
+        }
+
+        async fn use_token(
+            &self,
+            hash: &str,
+            purpose: TokenPurpose,
+        ) -> Result<Option<i32>, SQLError> {
+            let mut tokens = self.tokens.lock().unwrap();
+            match tokens.iter_mut().find(|token| {
+                token.hash == hash
+                    && token.purpose == purpose
+                    && !token.used
+                    && token.expires_at > Utc::now()
+            }) {
+                Some(token) => {
+                    token.used = true;
+                    Ok(Some(token.user_id))
+                }
+                None => Ok(None),
+            }
+        }
+    }
+
+    /// Personal access token repository keeping tokens with their hashes in memory, used for testing.
+    #[derive(Clone, Default)]
+    struct InMemoryPersonalAccessTokenRepository {
+        tokens: Arc<Mutex<Vec<(PersonalAccessToken, String)>>>,
+    }
+
+    impl PersonalAccessTokenRepository for InMemoryPersonalAccessTokenRepository {
+        async fn add_token(&self, token: &PersonalAccessToken, hash: &str) -> Result<(), SQLError> {
+            self.tokens
+                .lock()
+                .unwrap()
+                .push((token.clone(), String::from(hash)));
+            Ok(())
+        }
+
+        async fn get_tokens_by_user_id(
+            &self,
+            user_id: i32,
+        ) -> Result<Vec<PersonalAccessToken>, SQLError> {
+            Ok(self
+                .tokens
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(token, _)| token.user_id == user_id)
+                .map(|(token, _)| token.clone())
+                .collect())
+        }
+
+        async fn delete_token(&self, id: Uuid, user_id: i32) -> Result<bool, SQLError> {
+            let mut tokens = self.tokens.lock().unwrap();
+            let len = tokens.len();
+            tokens.retain(|(token, _)| !(token.id == id && token.user_id == user_id));
+            Ok(tokens.len() != len)
+        }
+
+        async fn use_token(&self, hash: &str) -> Result<Option<PersonalAccessToken>, SQLError> {
+            let mut tokens = self.tokens.lock().unwrap();
+            match tokens.iter_mut().find(|(token, token_hash)| {
+                token_hash == hash && token.expires_at.is_none_or(|exp| exp > Utc::now())
+            }) {
+                Some((token, _)) => {
+                    token.last_used_at = Some(Utc::now());
+                    Ok(Some(token.clone()))
+                }
+                None => Ok(None),
+            }
+        }
+    }
+
+    /// Failed login attempts of a key stored by `InMemoryLoginAttemptRepository`.
+    struct StoredLoginFailures {
+        failures: i32,
+        last_failure_at: DateTime<Utc>,
+        locked_until: Option<DateTime<Utc>>,
+    }
+
+    /// Preferences repository keeping preferences in memory, used for testing.
+    #[derive(Clone, Default)]
+    struct InMemoryPreferencesRepository {
+        preferences: Arc<Mutex<HashMap<i32, Preferences>>>,
+    }
+
+    impl PreferencesRepository for InMemoryPreferencesRepository {
+        async fn get_preferences(&self, user_id: i32) -> Result<Preferences, SQLError> {
+            Ok(self
+                .preferences
+                .lock()
+                .unwrap()
+                .get(&user_id)
+                .cloned()
+                .unwrap_or_default())
+        }
+
+        async fn set_preferences(
+            &self,
+            user_id: i32,
+            preferences: &Preferences,
+        ) -> Result<bool, SQLError> {
+            if !["Low", "Medium", "High", "Vital"].contains(&preferences.default_priority.as_str())
+            {
+                return Ok(false);
+            }
+
+            self.preferences
+                .lock()
+                .unwrap()
+                .insert(user_id, preferences.clone());
+            Ok(true)
+        }
+    }
+
+    /// Login attempt repository keeping failures and lockout events in memory, used for testing.
+    #[derive(Clone, Default)]
+    struct InMemoryLoginAttemptRepository {
+        failures: Arc<Mutex<HashMap<String, StoredLoginFailures>>>,
+        /// Lockout events as `(key, failures)`.
+        events: Arc<Mutex<Vec<(String, i32)>>>,
+    }
+
+    impl LoginAttemptRepository for InMemoryLoginAttemptRepository {
+        async fn get_locked_until(
+            &self,
+            keys: &[String],
+        ) -> Result<Option<DateTime<Utc>>, SQLError> {
+            let failures = self.failures.lock().unwrap();
+            Ok(keys
+                .iter()
+                .filter_map(|key| failures.get(key).and_then(|stored| stored.locked_until))
+                .filter(|locked_until| *locked_until > Utc::now())
+                .max())
+        }
+
+        async fn add_failure(&self, key: &str, since: DateTime<Utc>) -> Result<i32, SQLError> {
+            let mut failures = self.failures.lock().unwrap();
+            let stored = failures
+                .entry(String::from(key))
+                .or_insert(StoredLoginFailures {
+                    failures: 0,
+                    last_failure_at: Utc::now(),
+                    locked_until: None,
+                });
+            if stored.last_failure_at < since {
+                stored.failures = 0;
+            }
+            stored.failures += 1;
+            stored.last_failure_at = Utc::now();
+            Ok(stored.failures)
+        }
+
+        async fn lock(
+            &self,
+            key: &str,
+            failures: i32,
+            locked_until: DateTime<Utc>,
+        ) -> Result<(), SQLError> {
+            if let Some(stored) = self.failures.lock().unwrap().get_mut(key) {
+                stored.locked_until = Some(locked_until);
+            }
+            self.events
+                .lock()
+                .unwrap()
+                .push((String::from(key), failures));
+            Ok(())
+        }
+
+        async fn clear_failures(&self, key: &str) -> Result<(), SQLError> {
+            self.failures.lock().unwrap().remove(key);
+            Ok(())
+        }
+    }
+
+    /// Mailer keeping the sent mails in memory, used for testing.
+    /// When `fail` is set sending fails.
+    #[derive(Clone, Default)]
+    struct InMemoryMailer {
+        mails: Arc<Mutex<Vec<Mail>>>,
+        fail: bool,
+    }
+
+    impl Mailer for InMemoryMailer {
+        async fn send(&self, mail: Mail) -> Result<(), crate::mail::MailError> {
+            if self.fail {
+                return Err(crate::mail::MailError::new("unreachable server"));
+            }
+            self.mails.lock().unwrap().push(mail);
+            Ok(())
+        }
+    }
+
+    type TestService = DefaultUserService<
+        InMemoryUserRepository,
+        InMemoryTokenRepository,
+        InMemoryOneTimeTokenRepository,
+        InMemoryPersonalAccessTokenRepository,
+        InMemoryLoginAttemptRepository,
+        InMemoryPreferencesRepository,
+        InMemoryMailer,
+    >;
+
+    const EMAIL: &str = "email@example.com";
+    const PASSWORD: &str = "Password_0123";
+
+    /// Function that will create settings with the cheapest password hashing, so the tests are fast.
+    fn test_settings() -> UserSettings {
+        UserSettings {
+            password_hasher: PasswordHasher {
+                algorithm: PasswordAlgorithm::Argon2id,
+                argon2_memory: 8,
+                argon2_iterations: 1,
+                bcrypt_cost: 4,
+            },
+            ..UserSettings::default()
+        }
+    }
+
+    /// Function that will create a service with one registered user.
+    async fn registered_service() -> (TestService, InMemoryTokenRepository) {
+        registered_service_with(UserSettings {
+            password_reset_url: Some(String::from("https://tasks.example.com/reset")),
+            ..test_settings()
+        })
+        .await
+    }
+
+    /// Function that will create a service with `settings` and one registered user.
+    async fn registered_service_with(
+        settings: UserSettings,
+    ) -> (TestService, InMemoryTokenRepository) {
+        let tokens = InMemoryTokenRepository::default();
+        let one_time_tokens = InMemoryOneTimeTokenRepository::default();
+        let login_attempts = InMemoryLoginAttemptRepository::default();
+        let service = DefaultUserService::new(
+            Arc::new(InMemoryUserRepository {
+                one_time_tokens: one_time_tokens.clone(),
+                tokens: tokens.clone(),
+                login_attempts: login_attempts.clone(),
+                ..InMemoryUserRepository::default()
+            }),
+            Arc::new(tokens.clone()),
+            Arc::new(one_time_tokens),
+            Arc::new(InMemoryPersonalAccessTokenRepository::default()),
+            Arc::new(login_attempts),
+            Arc::new(InMemoryPreferencesRepository::default()),
+            InMemoryMailer::default(),
+            Arc::new(Authenticator::new(
+                String::from("secret"),
+                auth::TokenSettings::default(),
+            )),
+            settings,
+        )
+        .unwrap();
+
+        service
+            .register(&mut UserPayload {
+                email: String::from(EMAIL),
+                username: String::from("Valid_Username"),
+                password: String::from(PASSWORD),
+            })
+            .await
+            .unwrap();
+
+        (service, tokens)
+    }
+
+    async fn login_with(service: &TestService, password: &str) -> APIResult<LoginResponse> {
+        service
+            .login(
+                &LoginPayload {
+                    identifier: String::from(EMAIL),
+                    password: String::from(password),
+                },
+                &ClientInfo::default(),
+            )
+            .await
+    }
+
+    async fn login(service: &TestService) -> TokenGroup {
+        match login_with(service, PASSWORD).await.unwrap() {
+            LoginResponse::Tokens(group) => group,
+            LoginResponse::Challenge(_) => panic!("Expected tokens"),
+        }
+    }
+
+    /// Function that will return the token from the last sent mail.
+    fn mailed_token(service: &TestService) -> String {
+        let mails = service.mailer.mails.lock().unwrap();
+        let body = &mails.last().unwrap().body;
+        let token = body.split("Token: ").nth(1).unwrap();
+        String::from(token.lines().next().unwrap())
+    }
+
+    fn refresh_claims(service: &TestService, group: &TokenGroup) -> auth::RefreshClaims {
+        service
+            .authenticator
+            .verify_refresh_token(&group.refresh_token)
+            .unwrap()
+    }
+
+    fn access_claims(service: &TestService, group: &TokenGroup) -> auth::AccessClaims {
+        service
+            .authenticator
+            .verify_access_token(&group.access_token)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_logout() {
+        let (service, tokens) = registered_service().await;
+        let group = login(&service).await;
+        let other = login(&service).await;
+        assert_eq!(tokens.count(1), 2);
+
+        service
+            .logout(refresh_claims(&service, &group))
+            .await
+            .unwrap();
+        assert_eq!(tokens.count(1), 1);
+
+        let unauthorized =
+            APIErrorResponse::new(StatusCode::UNAUTHORIZED, String::from("Unauthorized"));
+        assert_eq!(
+            service
+                .refresh(refresh_claims(&service, &group), &ClientInfo::default())
+                .await
+                .err(),
+            Some(unauthorized)
+        );
+        assert!(service
+            .refresh(refresh_claims(&service, &other), &ClientInfo::default())
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_refresh_reuse_revokes_family() {
+        let (service, tokens) = registered_service().await;
+        let stolen = login(&service).await;
+        let other = login(&service).await;
+
+        let rotated = service
+            .refresh(refresh_claims(&service, &stolen), &ClientInfo::default())
+            .await
+            .unwrap();
+        assert_eq!(tokens.count(1), 2);
+
+        let unauthorized =
+            APIErrorResponse::new(StatusCode::UNAUTHORIZED, String::from("Unauthorized"));
+        assert_eq!(
+            service
+                .refresh(refresh_claims(&service, &stolen), &ClientInfo::default())
+                .await
+                .err(),
+            Some(unauthorized)
+        );
+        assert_eq!(tokens.count(1), 1);
+        assert!(service
+            .refresh(refresh_claims(&service, &rotated), &ClientInfo::default())
+            .await
+            .is_err());
+        assert!(service
+            .refresh(refresh_claims(&service, &other), &ClientInfo::default())
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_logout_all() {
+        let (service, tokens) = registered_service().await;
+        let group = login(&service).await;
+        let other = login(&service).await;
+
+        service
+            .logout_all(access_claims(&service, &group))
+            .await
+            .unwrap();
+        assert_eq!(tokens.count(1), 0);
+        assert!(service
+            .refresh(refresh_claims(&service, &other), &ClientInfo::default())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sessions() {
+        let (service, _) = registered_service().await;
+        let group = login(&service).await;
+        let other = login(&service).await;
+
+        let client = ClientInfo::new(Some(String::from("curl/8.0")), "10.0.0.1".parse().ok());
+        service
+            .refresh(refresh_claims(&service, &group), &client)
+            .await
+            .unwrap();
+
+        let claims = access_claims(&service, &group);
+        let sessions = service.get_sessions(claims.clone()).await.unwrap();
+        assert_eq!(sessions.len(), 2);
+        let refreshed = sessions
+            .iter()
+            .find(|session| session.user_agent.is_some())
+            .unwrap();
+        assert_eq!(refreshed.user_agent.as_deref(), Some("curl/8.0"));
+        assert_eq!(refreshed.ip.as_deref(), Some("10.0.0.1"));
+
+        assert_eq!(
+            service
+                .revoke_session(
+                    refreshed.id,
+                    auth::AccessClaims::new(2, 0, 0, String::new(), String::new(), vec![])
+                )
+                .await,
+            Err(APIErrorResponse::new(
+                StatusCode::NOT_FOUND,
+                String::from("Session not found"),
+            ))
+        );
+        service
+            .revoke_session(refreshed.id, claims.clone())
+            .await
+            .unwrap();
+
+        let sessions = service.get_sessions(claims).await.unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert!(service
+            .refresh(refresh_claims(&service, &other), &ClientInfo::default())
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_login_wrong_password() {
+        let (service, tokens) = registered_service().await;
+        assert_eq!(
+            login_with(&service, "Wrong_Password_1").await.err(),
+            Some(APIErrorResponse::new(
+                StatusCode::UNAUTHORIZED,
+                String::from("Invalid login or password"),
+            ))
+        );
+        assert_eq!(tokens.count(1), 0);
+    }
+
+    #[tokio::test]
+    async fn test_login_with_username() {
+        let (service, _) = registered_service_with(UserSettings {
+            lockout: LockoutSettings {
+                max_failures: 2,
+                ..LockoutSettings::default()
+            },
+            ..test_settings()
+        })
+        .await;
+        let attempt = |identifier: &str, password: &str| LoginPayload {
+            identifier: String::from(identifier),
+            password: String::from(password),
+        };
+        let invalid = || {
+            Err(APIErrorResponse::new(
+                StatusCode::UNAUTHORIZED,
+                String::from("Invalid login or password"),
+            ))
+        };
+
+        assert!(service
+            .login(&attempt("Valid_Username", PASSWORD), &ClientInfo::default())
+            .await
+            .is_ok());
+        assert_eq!(
+            service
+                .login(&attempt("Other_Username", PASSWORD), &ClientInfo::default())
+                .await
+                .map(|_| ()),
+            invalid()
+        );
+
+        // Failures by username and by email count towards the same account.
+        assert_eq!(
+            service
+                .login(
+                    &attempt("Valid_Username", "Wrong_Password_0"),
+                    &ClientInfo::default()
+                )
+                .await
+                .map(|_| ()),
+            invalid()
+        );
+        login_with(&service, "Wrong_Password_0").await.unwrap_err();
+        assert_eq!(
+            *service.login_attempt_repository.events.lock().unwrap(),
+            vec![(account_login_key(EMAIL), 2)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_change_password() {
+        let (service, tokens) = registered_service().await;
+        let group = login(&service).await;
+        let other = login(&service).await;
+
+        assert_eq!(
+            service
+                .change_password(
+                    &PasswordChangePayload {
+                        current_password: String::from("Wrong_Password_1"),
+                        new_password: String::from("New_Password_1"),
+                    },
+                    access_claims(&service, &group),
+                    &ClientInfo::default(),
+                )
+                .await
+                .err(),
+            Some(APIErrorResponse::new(
+                StatusCode::FORBIDDEN,
+                String::from("Invalid current password"),
+            ))
+        );
+        assert_eq!(tokens.count(1), 2);
+
+        let changed = service
+            .change_password(
+                &PasswordChangePayload {
+                    current_password: String::from(PASSWORD),
+                    new_password: String::from("New_Password_1"),
+                },
+                access_claims(&service, &group),
+                &ClientInfo::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(tokens.count(1), 1);
+        assert!(service
+            .refresh(refresh_claims(&service, &other), &ClientInfo::default())
+            .await
+            .is_err());
+        assert!(service
+            .refresh(refresh_claims(&service, &changed), &ClientInfo::default())
+            .await
+            .is_ok());
+
+        assert!(login_with(&service, PASSWORD).await.is_err());
+        assert!(login_with(&service, "New_Password_1").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_password_reset() {
+        let (service, tokens) = registered_service().await;
+        let group = login(&service).await;
+
+        service
+            .send_password_reset_mail("unknown@example.com")
+            .await
+            .unwrap();
+        assert_eq!(service.mailer.mails.lock().unwrap().len(), 1);
+
+        service.send_password_reset_mail(EMAIL).await.unwrap();
+        let replaced = mailed_token(&service);
+        service.send_password_reset_mail(EMAIL).await.unwrap();
+        let token = mailed_token(&service);
+        {
+            let mails = service.mailer.mails.lock().unwrap();
+            let mail = mails.last().unwrap();
+            assert_eq!(mail.to, EMAIL);
+            assert!(mail
+                .body
+                .contains(&format!("https://tasks.example.com/reset?token={}", token)));
+        }
+
+        let invalid = Some(APIErrorResponse::new(
+            StatusCode::BAD_REQUEST,
+            String::from("Invalid or expired token"),
+        ));
+        let payload = |token: &str| PasswordResetPayload {
+            token: String::from(token),
+            new_password: String::from("New_Password_1"),
+        };
+        assert_eq!(
+            service.reset_password(&payload(&replaced)).await.err(),
+            invalid
+        );
+
+        service.reset_password(&payload(&token)).await.unwrap();
+        assert_eq!(
+            service.reset_password(&payload(&token)).await.err(),
+            invalid
+        );
+        assert_eq!(tokens.count(1), 0);
+        assert!(service
+            .refresh(refresh_claims(&service, &group), &ClientInfo::default())
+            .await
+            .is_err());
+        assert!(login_with(&service, PASSWORD).await.is_err());
+        assert!(login_with(&service, "New_Password_1").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_password_reset_mail_failure() {
+        let (mut service, _) = registered_service().await;
+        service.mailer.fail = true;
+        assert!(service.send_password_reset_mail(EMAIL).await.is_err());
+
+        // A failing mail of a registered email looks the same as an unknown email.
+        let unknown = service.request_password_reset("unknown@example.com").await;
+        assert_eq!(unknown, Ok(()));
+        assert_eq!(service.request_password_reset(EMAIL).await, unknown);
+    }
+
+    #[tokio::test]
+    async fn test_password_reset_expired() {
+        let (service, _) = registered_service().await;
+        let token = OneTimeToken::generate();
+        service
+            .one_time_token_repository
+            .add_token(
+                &token.hash,
+                1,
+                TokenPurpose::PasswordReset,
+                Utc::now() - Duration::minutes(1),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            service
+                .reset_password(&PasswordResetPayload {
+                    token: token.token,
+                    new_password: String::from("New_Password_1"),
+                })
+                .await
+                .err(),
+            Some(APIErrorResponse::new(
+                StatusCode::BAD_REQUEST,
+                String::from("Invalid or expired token"),
+            ))
+        );
+        assert!(login_with(&service, PASSWORD).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_update_current_user() {
+        let (service, _) = registered_service().await;
+        service.verify_email(&mailed_token(&service)).await.unwrap();
+        service
+            .register(&mut UserPayload {
+                email: String::from("other@example.com"),
+                username: String::from("Other_Username"),
+                password: String::from(PASSWORD),
+            })
+            .await
+            .unwrap();
+        let claims = access_claims(&service, &login(&service).await);
+
+        let user = service.get_current_user(claims.clone()).await.unwrap();
+        assert_eq!(user.email, EMAIL);
+        assert_eq!(user.username, "Valid_Username");
+        assert!(user.email_verified);
+
+        let conflict = Err(APIErrorResponse::new(
+            StatusCode::CONFLICT,
+            String::from("Email or username already exists"),
+        ));
+        let update = |email: Option<&str>, username: Option<&str>| UserUpdatePayload {
+            email: email.map(String::from),
+            username: username.map(String::from),
+        };
+        assert_eq!(
+            service
+                .update_current_user(&update(None, Some("Other_Username")), claims.clone())
+                .await
+                .map(|_| ()),
+            conflict
+        );
+        assert_eq!(
+            service
+                .update_current_user(&update(Some("other@example.com"), None), claims.clone())
+                .await
+                .map(|_| ()),
+            conflict
+        );
+
+        // Keeping own username isn't a conflict and no mail is sent.
+        let mails = service.mailer.mails.lock().unwrap().len();
+        let user = service
+            .update_current_user(&update(None, Some("Valid_Username")), claims.clone())
+            .await
+            .unwrap();
+        assert!(user.email_verified);
+        assert_eq!(service.mailer.mails.lock().unwrap().len(), mails);
+
+        // A new email has to be verified again.
+        let user = service
+            .update_current_user(&update(Some("new@example.com"), None), claims)
+            .await
+            .unwrap();
+        assert_eq!(user.email, "new@example.com");
+        assert_eq!(user.username, "Valid_Username");
+        assert!(!user.email_verified);
+        assert_eq!(
+            service.mailer.mails.lock().unwrap().last().unwrap().to,
+            "new@example.com"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_current_user() {
+        let (service, _) = registered_service().await;
+        let claims = access_claims(&service, &login(&service).await);
+
+        assert_eq!(
+            service
+                .delete_current_user("Wrong_Password_0", claims.clone())
+                .await,
+            Err(APIErrorResponse::new(
+                StatusCode::FORBIDDEN,
+                String::from("Invalid password"),
+            ))
+        );
+        assert!(service.get_current_user(claims.clone()).await.is_ok());
+
+        let key = account_login_key(EMAIL);
+        login_with(&service, "Wrong_Password_0").await.unwrap_err();
+        service
+            .login_attempt_repository
+            .lock(&key, 1, Utc::now())
+            .await
+            .unwrap();
+
+        service
+            .delete_current_user(PASSWORD, claims.clone())
+            .await
+            .unwrap();
+        // Nothing keyed by the email is left behind.
+        let login_attempts = &service.login_attempt_repository;
+        assert!(!login_attempts.failures.lock().unwrap().contains_key(&key));
+        assert!(login_attempts.events.lock().unwrap().is_empty());
+        let unauthorized = Err(APIErrorResponse::new(
+            StatusCode::UNAUTHORIZED,
+            String::from("Unauthorized"),
+        ));
+        assert_eq!(
+            service.get_current_user(claims.clone()).await.map(|_| ()),
+            unauthorized
+        );
+        assert_eq!(
+            service.delete_current_user(PASSWORD, claims).await,
+            unauthorized
+        );
+        assert!(login_with(&service, PASSWORD).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_preferences() {
+        let (service, _) = registered_service().await;
+        let claims = access_claims(&service, &login(&service).await);
+
+        assert_eq!(
+            service.get_preferences(claims.clone()).await,
+            Ok(Preferences::default())
+        );
+
+        let preferences = service
+            .update_preferences(
+                &PreferencesPayload {
+                    timezone: Some(String::from("Europe/Berlin")),
+                    default_priority: Some(String::from("High")),
+                    ..Default::default()
+                },
+                claims.clone(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(preferences.timezone, "Europe/Berlin");
+        assert_eq!(preferences.locale, "en-US");
+        assert_eq!(preferences.default_priority, "High");
+
+        assert_eq!(
+            service
+                .update_preferences(
+                    &PreferencesPayload {
+                        locale: Some(String::from("de-DE")),
+                        default_priority: Some(String::from("Urgent")),
+                        ..Default::default()
+                    },
+                    claims.clone(),
+                )
+                .await,
+            Err(APIErrorResponse::new(
+                StatusCode::BAD_REQUEST,
+                String::from("Invalid priority"),
+            ))
+        );
+        assert_eq!(service.get_preferences(claims).await, Ok(preferences));
+    }
+
+    #[tokio::test]
+    async fn test_verify_email() {
+        let (service, _) = registered_service_with(UserSettings {
+            email_verification_url: Some(String::from(
+                "https://tasks.example.com/api/v1/users/verify-email",
+            )),
+            require_verified_email: true,
+            ..test_settings()
+        })
+        .await;
+
+        let first = mailed_token(&service);
+        {
+            let mails = service.mailer.mails.lock().unwrap();
+            let mail = mails.last().unwrap();
+            assert_eq!(mail.to, EMAIL);
+            assert_eq!(mail.subject, "Verify your email");
+            assert!(mail.body.contains(&format!(
+                "https://tasks.example.com/api/v1/users/verify-email?token={}",
+                first
+            )));
+        }
+
+        assert_eq!(
+            login_with(&service, PASSWORD).await.err(),
+            Some(APIErrorResponse::new(
+                StatusCode::FORBIDDEN,
+                String::from("Email is not verified"),
+            ))
+        );
+
+        service.resend_verification_mail(EMAIL).await.unwrap();
+        let token = mailed_token(&service);
+        let invalid = Some(APIErrorResponse::new(
+            StatusCode::BAD_REQUEST,
+            String::from("Invalid or expired token"),
+        ));
+        assert_eq!(service.verify_email(&first).await.err(), invalid);
+
+        service.verify_email(&token).await.unwrap();
+        assert_eq!(service.verify_email(&token).await.err(), invalid);
+        assert!(login_with(&service, PASSWORD).await.is_ok());
+
+        service.resend_verification_mail(EMAIL).await.unwrap();
+        service
+            .resend_verification_mail("unknown@example.com")
+            .await
+            .unwrap();
+        assert_eq!(service.mailer.mails.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_resend_verification_mail_failure() {
+        let (mut service, _) = registered_service().await;
+        service.mailer.fail = true;
+        assert!(service.resend_verification_mail(EMAIL).await.is_err());
+
+        // A failing mail of a registered email looks the same as an unknown email.
+        let unknown = service.resend_verification("unknown@example.com").await;
+        assert_eq!(unknown, Ok(()));
+        assert_eq!(service.resend_verification(EMAIL).await, unknown);
+    }
+
+    #[tokio::test]
+    async fn test_login_unverified_email_allowed_by_default() {
+        let (service, _) = registered_service().await;
+        assert!(login_with(&service, PASSWORD).await.is_ok());
+    }
+
+    /// Function that will generate TOTP code for `secret` at `time`.
+    fn totp_code(secret: &str, time: i64) -> String {
+        let bytes = Secret::Encoded(String::from(secret)).to_bytes().unwrap();
+        TOTP::new_unchecked(
+            totp_rs::Algorithm::SHA1,
+            6,
+            0,
+            30,
+            bytes,
+            None,
+            String::new(),
+        )
+        .generate(time as u64)
+    }
+
+    #[tokio::test]
+    async fn test_two_factor() {
+        let (service, _) = registered_service().await;
+        let group = login(&service).await;
+        let claims = access_claims(&service, &group);
+
+        assert_eq!(
+            service
+                .enable_two_factor("123456", claims.clone())
+                .await
+                .err(),
+            Some(APIErrorResponse::new(
+                StatusCode::CONFLICT,
+                String::from("Two-factor authentication isn't set up"),
+            ))
+        );
+
+        let setup = service.setup_two_factor(claims.clone()).await.unwrap();
+        assert!(setup
+            .otpauth_uri
+            .starts_with("otpauth://totp/Tasks:email%40example.com?secret="));
+
+        let invalid_code = Some(APIErrorResponse::new(
+            StatusCode::BAD_REQUEST,
+            String::from("Invalid code"),
+        ));
+        assert_eq!(
+            service
+                .enable_two_factor("12345", claims.clone())
+                .await
+                .err(),
+            invalid_code
+        );
+
+        let now = Utc::now().timestamp();
+        let code = totp_code(&setup.secret, now);
+        let codes = service
+            .enable_two_factor(&code, claims.clone())
+            .await
+            .unwrap()
+            .recovery_codes;
+        assert_eq!(codes.len(), 10);
+        assert!(service.setup_two_factor(claims.clone()).await.is_err());
+
+        let challenge = match login_with(&service, PASSWORD).await.unwrap() {
+            LoginResponse::Challenge(challenge) => challenge.challenge_token,
+            LoginResponse::Tokens(_) => panic!("Expected challenge"),
+        };
+        let login_two_factor = |challenge_token: &str, code: &str| {
+            let payload = TwoFactorLoginPayload {
+                challenge_token: String::from(challenge_token),
+                code: String::from(code),
+            };
+            let service = service.clone();
+            async move {
+                service
+                    .login_two_factor(&payload, &ClientInfo::default())
+                    .await
+            }
+        };
+
+        let invalid_code = Some(APIErrorResponse::new(
+            StatusCode::UNAUTHORIZED,
+            String::from("Invalid code"),
+        ));
+        assert_eq!(
+            login_two_factor(&challenge, &code).await.err(),
+            invalid_code
+        );
+        assert!(
+            login_two_factor(&challenge, &totp_code(&setup.secret, now + 30))
+                .await
+                .is_ok()
+        );
+
+        let recovery_code = codes[0].to_uppercase();
+        assert!(login_two_factor(&challenge, &recovery_code).await.is_ok());
+        assert_eq!(
+            login_two_factor(&challenge, &recovery_code).await.err(),
+            invalid_code
+        );
+        assert_eq!(
+            login_two_factor(&group.access_token, &codes[1]).await.err(),
+            Some(APIErrorResponse::new(
+                StatusCode::UNAUTHORIZED,
+                String::from("Unauthorized"),
+            ))
+        );
+
+        assert_eq!(
+            service
+                .disable_two_factor("Wrong_Password_1", claims.clone())
+                .await
+                .err(),
+            Some(APIErrorResponse::new(
+                StatusCode::FORBIDDEN,
+                String::from("Invalid password"),
+            ))
+        );
+        service
+            .disable_two_factor(PASSWORD, claims.clone())
+            .await
+            .unwrap();
+        assert!(login_two_factor(&challenge, &codes[1]).await.is_err());
+        login(&service).await;
+    }
+
+    #[tokio::test]
+    async fn test_personal_access_tokens() {
+        let (service, _) = registered_service().await;
+        let claims = access_claims(&service, &login(&service).await);
+
+        let created = service
+            .create_personal_access_token(
+                &PersonalAccessTokenPayload {
+                    name: String::from(" ci "),
+                    scopes: None,
+                    expires_at: None,
+                },
+                claims.clone(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(created.personal_access_token.name, "ci");
+        assert_eq!(created.personal_access_token.scopes, Scope::ALL.to_vec());
+
+        let verified = service
+            .verify_personal_access_token(&created.token)
+            .await
+            .unwrap();
+        assert_eq!(verified.sub, claims.sub);
+        assert!(service
+            .verify_personal_access_token("tsk_unknown")
+            .await
+            .is_err());
+
+        let tokens = service
+            .get_personal_access_tokens(claims.clone())
+            .await
+            .unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert!(tokens[0].last_used_at.is_some());
+
+        let other_user = auth::AccessClaims {
+            sub: claims.sub + 1,
+            ..claims.clone()
+        };
+        let error = service
+            .revoke_personal_access_token(created.personal_access_token.id, other_user)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error,
+            APIErrorResponse::new(StatusCode::NOT_FOUND, String::from("Token not found"))
+        );
+
+        service
+            .revoke_personal_access_token(created.personal_access_token.id, claims.clone())
+            .await
+            .unwrap();
+        assert!(service
+            .verify_personal_access_token(&created.token)
+            .await
+            .is_err());
+        assert!(service
+            .get_personal_access_tokens(claims)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_expired_personal_access_token() {
+        let (service, _) = registered_service().await;
+        let claims = access_claims(&service, &login(&service).await);
+        let created = service
+            .create_personal_access_token(
+                &PersonalAccessTokenPayload {
+                    name: String::from("ci"),
+                    scopes: Some(vec![Scope::TasksRead]),
+                    expires_at: Some(Utc::now() + Duration::days(1)),
+                },
+                claims,
+            )
+            .await
+            .unwrap();
+        assert!(service
+            .verify_personal_access_token(&created.token)
+            .await
+            .is_ok());
+
+        service
+            .personal_access_token_repository
+            .tokens
+            .lock()
+            .unwrap()[0]
+            .0
+            .expires_at = Some(Utc::now() - Duration::seconds(1));
+        assert!(service
+            .verify_personal_access_token(&created.token)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_personal_access_token_scopes() {
+        let (service, _) = registered_service().await;
+        let claims = access_claims(&service, &login(&service).await);
+        let payload = |scopes: Option<Vec<Scope>>| PersonalAccessTokenPayload {
+            name: String::from("ci"),
+            scopes,
+            expires_at: None,
+        };
+
+        let created = service
+            .create_personal_access_token(
+                &payload(Some(vec![Scope::TasksRead, Scope::AccountManage])),
+                claims,
+            )
+            .await
+            .unwrap();
+        let token_claims = service
+            .verify_personal_access_token(&created.token)
+            .await
+            .unwrap();
+        assert!(token_claims.has_scope(Scope::TasksRead));
+        assert!(!token_claims.has_scope(Scope::TasksWrite));
+
+        let error = service
+            .create_personal_access_token(
+                &payload(Some(vec![Scope::TasksWrite])),
+                token_claims.clone(),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error,
+            APIErrorResponse::new(
+                StatusCode::FORBIDDEN,
+                String::from("Missing scope tasks:write")
+            )
+        );
+
+        let inherited = service
+            .create_personal_access_token(&payload(None), token_claims)
+            .await
+            .unwrap();
+        assert_eq!(
+            inherited.personal_access_token.scopes,
+            vec![Scope::TasksRead, Scope::AccountManage]
+        );
+    }
+
+    #[test]
+    fn test_lockout_after() {
+        let settings = LockoutSettings::default();
+        assert_eq!(settings.lockout_after(4, 5), None);
+        assert_eq!(settings.lockout_after(5, 5), Some(Duration::minutes(1)));
+        assert_eq!(settings.lockout_after(6, 5), Some(Duration::minutes(2)));
+        assert_eq!(settings.lockout_after(8, 5), Some(Duration::minutes(8)));
+        assert_eq!(settings.lockout_after(12, 5), Some(Duration::hours(1)));
+        assert_eq!(settings.lockout_after(1000, 5), Some(Duration::hours(1)));
+    }
+
+    #[tokio::test]
+    async fn test_login_lockout() {
+        let (service, _) = registered_service_with(UserSettings {
+            lockout: LockoutSettings {
+                max_failures: 3,
+                ..LockoutSettings::default()
+            },
+            ..test_settings()
+        })
+        .await;
+        let locked = || -> APIResult<()> {
+            Err(APIErrorResponse::new(
+                StatusCode::TOO_MANY_REQUESTS,
+                String::from("Too many failed login attempts"),
+            )
+            .with_retry_after(60))
+        };
+
+        // A successful login forgets the previous failures.
+        login_with(&service, "Wrong_Password_0").await.unwrap_err();
+        login_with(&service, "Wrong_Password_0").await.unwrap_err();
+        login(&service).await;
+        login_with(&service, "Wrong_Password_0").await.unwrap_err();
+        login_with(&service, "Wrong_Password_0").await.unwrap_err();
+        assert!(service
+            .login_attempt_repository
+            .events
+            .lock()
+            .unwrap()
+            .is_empty());
+
+        assert_eq!(
+            login_with(&service, "Wrong_Password_0").await.unwrap_err(),
+            APIErrorResponse::new(
+                StatusCode::UNAUTHORIZED,
+                String::from("Invalid login or password")
+            )
+        );
+        assert_eq!(
+            *service.login_attempt_repository.events.lock().unwrap(),
+            vec![(account_login_key(EMAIL), 3)]
+        );
+
+        // The account stays locked even with the correct password and differently cased email.
+        assert_eq!(login_with(&service, PASSWORD).await.map(|_| ()), locked());
+        let result = service
+            .login(
+                &LoginPayload {
+                    identifier: EMAIL.to_uppercase(),
+                    password: String::from(PASSWORD),
+                },
+                &ClientInfo::default(),
+            )
+            .await;
+        assert_eq!(result.map(|_| ()), locked());
+
+        // The failures are counted again after the lockout ends, doubling it.
+        service
+            .login_attempt_repository
+            .failures
+            .lock()
+            .unwrap()
+            .get_mut(&account_login_key(EMAIL))
+            .unwrap()
+            .locked_until = None;
+        login_with(&service, "Wrong_Password_0").await.unwrap_err();
+        assert_eq!(
+            service.login_attempt_repository.events.lock().unwrap()[1],
+            (account_login_key(EMAIL), 4)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_login_lockout_per_ip() {
+        let (service, _) = registered_service_with(UserSettings {
+            lockout: LockoutSettings {
+                max_failures_per_ip: 2,
+                ..LockoutSettings::default()
+            },
+            ..test_settings()
+        })
+        .await;
+        let client = ClientInfo::new(None, Some("10.0.0.1".parse().unwrap()));
+        let attempt = |email: &str, password: &str| LoginPayload {
+            identifier: String::from(email),
+            password: String::from(password),
+        };
+
+        for email in ["first@example.com", "second@example.com"] {
+            service
+                .login(&attempt(email, PASSWORD), &client)
+                .await
+                .unwrap_err();
+        }
+
+        let error = service
+            .login(&attempt(EMAIL, PASSWORD), &client)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error,
+            APIErrorResponse::new(
+                StatusCode::TOO_MANY_REQUESTS,
+                String::from("Too many failed login attempts")
+            )
+            .with_retry_after(60)
+        );
+        assert!(service
+            .login(&attempt(EMAIL, PASSWORD), &ClientInfo::default())
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_login_upgrades_password_hash() {
+        let (mut service, _) = registered_service_with(UserSettings {
+            password_hasher: PasswordHasher {
+                algorithm: PasswordAlgorithm::Bcrypt,
+                ..test_settings().password_hasher
+            },
+            ..test_settings()
+        })
+        .await;
+        let stored_hash = |service: &TestService| {
+            service.user_repository.users.lock().unwrap()[0]
+                .password
+                .clone()
+        };
+        assert!(stored_hash(&service).starts_with("$2"));
+
+        // A failed login leaves the hash as it is.
+        service.settings.password_hasher.algorithm = PasswordAlgorithm::Argon2id;
+        login_with(&service, "Wrong_Password_0").await.unwrap_err();
+        assert!(stored_hash(&service).starts_with("$2"));
+
+        login(&service).await;
+        let upgraded = stored_hash(&service);
+        assert!(upgraded.starts_with("$argon2id$v=19$m=8,t=1,p=1$"));
+
+        login(&service).await;
+        assert_eq!(stored_hash(&service), upgraded);
     }
 }
@@ -1,2 +1,5 @@
 pub mod api_error_response;
-pub mod clean_tokens;
\ No newline at end of file
+pub mod clean_tokens;
+pub mod totp;
+pub mod password;
+pub mod breached_passwords;
\ No newline at end of file
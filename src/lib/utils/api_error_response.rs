@@ -1,3 +1,4 @@
+use axum::http::header::RETRY_AFTER;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use axum::Json;
@@ -9,10 +10,22 @@ use serde::{Serialize, Serializer};
 pub struct APIErrorResponse {
     status: StatusCode,
     message: String,
+    /// Seconds sent in the `Retry-After` header.
+    retry_after: Option<u64>,
 }
 impl APIErrorResponse {
     pub fn new(status: StatusCode, message: String) -> Self {
-        Self { status, message }
+        Self {
+            status,
+            message,
+            retry_after: None,
+        }
+    }
+
+    /// Method that will tell the client to retry the request after `seconds`.
+    pub fn with_retry_after(mut self, seconds: u64) -> Self {
+        self.retry_after = Some(seconds);
+        self
     }
 }
 
@@ -30,7 +43,10 @@ impl Serialize for APIErrorResponse {
 
 impl IntoResponse for APIErrorResponse {
     fn into_response(self) -> Response {
-        (self.status, Json(self)).into_response()
+        match self.retry_after {
+            Some(seconds) => (self.status, [(RETRY_AFTER, seconds)], Json(self)).into_response(),
+            None => (self.status, Json(self)).into_response(),
+        }
     }
 }
 
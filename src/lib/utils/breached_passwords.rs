@@ -0,0 +1,115 @@
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+/// `BreachedPasswords` is set of breached or common passwords, compared without regard to case.
+/// Only the first 8 bytes of the SHA-256 of each password are kept in a sorted list,
+/// so large lists stay compact and lookups are binary searches.
+#[derive(Clone, Default, PartialEq)]
+pub struct BreachedPasswords {
+    hashes: Vec<u64>,
+}
+
+/// Function that will hash the lowercase password into the key of the set.
+fn hash(password: &str) -> u64 {
+    let digest = Sha256::digest(password.to_lowercase().as_bytes());
+    let mut prefix = [0; 8];
+    prefix.copy_from_slice(&digest[..8]);
+    u64::from_be_bytes(prefix)
+}
+
+impl BreachedPasswords {
+    /// `new` method used to create set of the `passwords`, empty ones are skipped.
+    pub fn new<'a>(passwords: impl IntoIterator<Item = &'a str>) -> Self {
+        Self::from_hashes(
+            passwords
+                .into_iter()
+                .filter(|password| !password.is_empty())
+                .map(hash)
+                .collect(),
+        )
+    }
+
+    /// `from_file` method used to load the set from file with one password per line.
+    /// Lines that aren't valid UTF-8 can't match any password, so they are skipped.
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut hashes = Vec::new();
+        let mut line = Vec::new();
+        while reader.read_until(b'\n', &mut line)? > 0 {
+            if let Ok(password) = std::str::from_utf8(&line) {
+                let password = password.trim_end_matches(['\r', '\n']);
+                if !password.is_empty() {
+                    hashes.push(hash(password));
+                }
+            }
+            line.clear();
+        }
+
+        Ok(Self::from_hashes(hashes))
+    }
+
+    fn from_hashes(mut hashes: Vec<u64>) -> Self {
+        hashes.sort_unstable();
+        hashes.dedup();
+        hashes.shrink_to_fit();
+        Self { hashes }
+    }
+
+    /// Method that will check if the password is in the set.
+    pub fn contains(&self, password: &str) -> bool {
+        !self.hashes.is_empty() && self.hashes.binary_search(&hash(password)).is_ok()
+    }
+
+    /// Method returning the number of passwords in the set.
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+}
+
+impl fmt::Debug for BreachedPasswords {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BreachedPasswords")
+            .field("len", &self.hashes.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contains() {
+        let passwords = BreachedPasswords::new(["Password_123", "qwerty", "qwerty", ""]);
+        assert_eq!(passwords.len(), 2);
+        assert!(passwords.contains("Password_123"));
+        assert!(passwords.contains("password_123"));
+        assert!(passwords.contains("QWERTY"));
+        assert!(!passwords.contains("Password_1234"));
+        assert!(!passwords.contains(""));
+        assert!(!BreachedPasswords::default().contains("qwerty"));
+    }
+
+    #[test]
+    fn test_from_file() {
+        let path = std::env::temp_dir().join(format!("breached-{}.txt", uuid::Uuid::new_v4()));
+        let mut content = b"123456\r\npassword\n\nPassword_123\n".to_vec();
+        content.extend_from_slice(b"\xff\xfe\nletmein");
+        std::fs::write(&path, content).unwrap();
+
+        let passwords = BreachedPasswords::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            passwords,
+            BreachedPasswords::new(["123456", "password", "Password_123", "letmein"])
+        );
+        assert!(BreachedPasswords::from_file(&path).is_err());
+    }
+}
@@ -1,6 +1,7 @@
 use sqlx::PgPool;
 
-/// Function that will delete tokens that are expired.
+/// Function that will delete refresh tokens and one-time tokens that are expired
+/// and failed logins that are forgotten.
 /// When called it will run continuously and execute each 24h.
 pub fn clean_tokens(pool: PgPool) {
     tokio::spawn(async move {
@@ -17,6 +18,33 @@ pub fn clean_tokens(pool: PgPool) {
                     println!("Error while deleting tokens: {}", e);
                 }
             }
+
+            match sqlx::query("DELETE FROM one_time_tokens WHERE expires_at < NOW()")
+                .execute(&pool)
+                .await
+            {
+                Ok(result) => {
+                    println!("Deleted one-time tokens: {}", result.rows_affected());
+                }
+                Err(e) => {
+                    println!("Error while deleting one-time tokens: {}", e);
+                }
+            }
+
+            match sqlx::query(
+                "DELETE FROM login_failures WHERE last_failure_at < NOW() - INTERVAL '1 day' \
+                 AND (locked_until IS NULL OR locked_until < NOW())",
+            )
+            .execute(&pool)
+            .await
+            {
+                Ok(result) => {
+                    println!("Deleted login failures: {}", result.rows_affected());
+                }
+                Err(e) => {
+                    println!("Error while deleting login failures: {}", e);
+                }
+            }
         }
     });
 }
@@ -0,0 +1,183 @@
+use crate::utils::api_error_response::{APIErrorResponse, APIResult};
+use argon2::password_hash::SaltString;
+use argon2::{Algorithm, Argon2, Params, PasswordHash, Version};
+use axum::http::StatusCode;
+use rand::rngs::OsRng;
+
+/// Algorithm used to hash new passwords.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PasswordAlgorithm {
+    /// Argon2id stored as PHC string like `$argon2id$v=19$m=19456,t=2,p=1$...`.
+    Argon2id,
+    /// Bcrypt, which only uses the first 72 bytes of the password.
+    Bcrypt,
+}
+
+/// `PasswordHasher` hashes new passwords with the configured algorithm and cost,
+/// and verifies passwords against hashes of any supported algorithm.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PasswordHasher {
+    pub algorithm: PasswordAlgorithm,
+    /// Memory used by Argon2id in KiB.
+    pub argon2_memory: u32,
+    /// Number of Argon2id iterations.
+    pub argon2_iterations: u32,
+    /// Cost of bcrypt.
+    pub bcrypt_cost: u32,
+}
+
+impl Default for PasswordHasher {
+    fn default() -> Self {
+        Self {
+            algorithm: PasswordAlgorithm::Argon2id,
+            argon2_memory: Params::DEFAULT_M_COST,
+            argon2_iterations: Params::DEFAULT_T_COST,
+            bcrypt_cost: bcrypt::DEFAULT_COST,
+        }
+    }
+}
+
+/// Function that will create internal server error for hashing failure.
+fn hashing_error(err: impl ToString) -> APIErrorResponse {
+    APIErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+}
+
+impl PasswordHasher {
+    /// Method that will return the Argon2id parameters, with single lane.
+    fn argon2_params(&self) -> Result<Params, argon2::Error> {
+        Params::new(self.argon2_memory, self.argon2_iterations, 1, None)
+    }
+
+    /// Method that will hash the password with the configured algorithm.
+    pub fn hash(&self, password: &str) -> APIResult<String> {
+        match self.algorithm {
+            PasswordAlgorithm::Argon2id => {
+                let params = self.argon2_params().map_err(hashing_error)?;
+                let salt = SaltString::generate(&mut OsRng);
+                let hash = argon2::PasswordHasher::hash_password(
+                    &Argon2::new(Algorithm::Argon2id, Version::V0x13, params),
+                    password.as_bytes(),
+                    &salt,
+                )
+                .map_err(hashing_error)?;
+                Ok(hash.to_string())
+            }
+            PasswordAlgorithm::Bcrypt => {
+                bcrypt::hash(password, self.bcrypt_cost).map_err(hashing_error)
+            }
+        }
+    }
+
+    /// Method that will check the password against hash of any supported algorithm.
+    /// Malformed hashes are treated as not matching.
+    pub fn verify(&self, password: &str, hash: &str) -> bool {
+        if hash.starts_with("$argon2") {
+            return match PasswordHash::new(hash) {
+                Ok(parsed) => argon2::PasswordVerifier::verify_password(
+                    &Argon2::default(),
+                    password.as_bytes(),
+                    &parsed,
+                )
+                .is_ok(),
+                Err(_) => false,
+            };
+        }
+
+        bcrypt::verify(password, hash).unwrap_or(false)
+    }
+
+    /// Method that will check if the hash was made with another algorithm or cost than configured,
+    /// so it should be replaced with new hash once the password is known.
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        match self.algorithm {
+            PasswordAlgorithm::Argon2id => {
+                let Ok(parsed) = PasswordHash::new(hash) else {
+                    return true;
+                };
+                let Ok(params) = Params::try_from(&parsed) else {
+                    return true;
+                };
+
+                parsed.algorithm != Algorithm::Argon2id.ident()
+                    || parsed.version != Some(Version::V0x13.into())
+                    || params.m_cost() != self.argon2_memory
+                    || params.t_cost() != self.argon2_iterations
+                    || params.p_cost() != 1
+            }
+            PasswordAlgorithm::Bcrypt => match hash.parse::<bcrypt::HashParts>() {
+                Ok(parts) => parts.get_cost() != self.bcrypt_cost,
+                Err(_) => true,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Function that will create hasher with the lowest costs, so the tests are fast.
+    fn hasher(algorithm: PasswordAlgorithm) -> PasswordHasher {
+        PasswordHasher {
+            algorithm,
+            argon2_memory: 8,
+            argon2_iterations: 1,
+            bcrypt_cost: 4,
+        }
+    }
+
+    #[test]
+    fn test_hash_and_verify() {
+        for algorithm in [PasswordAlgorithm::Argon2id, PasswordAlgorithm::Bcrypt] {
+            let hasher = hasher(algorithm);
+            let hash = hasher.hash("Password_0123").unwrap();
+            assert!(hasher.verify("Password_0123", &hash));
+            assert!(!hasher.verify("Password_0124", &hash));
+            assert!(!hasher.needs_rehash(&hash));
+        }
+
+        assert!(hasher(PasswordAlgorithm::Argon2id)
+            .hash("Password_0123")
+            .unwrap()
+            .starts_with("$argon2id$v=19$m=8,t=1,p=1$"));
+        assert!(!hasher(PasswordAlgorithm::Bcrypt).verify("Password_0123", "malformed"));
+        assert!(!hasher(PasswordAlgorithm::Argon2id).verify("Password_0123", "$argon2id$"));
+    }
+
+    #[test]
+    fn test_argon2_uses_whole_password() {
+        let password = "a".repeat(72);
+        let longer = format!("{}b", password);
+
+        let bcrypt = hasher(PasswordAlgorithm::Bcrypt);
+        assert!(bcrypt.verify(&longer, &bcrypt.hash(&password).unwrap()));
+
+        let argon2 = hasher(PasswordAlgorithm::Argon2id);
+        assert!(!argon2.verify(&longer, &argon2.hash(&password).unwrap()));
+    }
+
+    #[test]
+    fn test_needs_rehash() {
+        let argon2 = hasher(PasswordAlgorithm::Argon2id);
+        let bcrypt = hasher(PasswordAlgorithm::Bcrypt);
+        let argon2_hash = argon2.hash("Password_0123").unwrap();
+        let bcrypt_hash = bcrypt.hash("Password_0123").unwrap();
+
+        // Both algorithms are verified regardless of the configured one.
+        assert!(argon2.verify("Password_0123", &bcrypt_hash));
+        assert!(bcrypt.verify("Password_0123", &argon2_hash));
+
+        assert!(argon2.needs_rehash(&bcrypt_hash));
+        assert!(bcrypt.needs_rehash(&argon2_hash));
+        assert!(PasswordHasher {
+            argon2_iterations: 2,
+            ..argon2.clone()
+        }
+        .needs_rehash(&argon2_hash));
+        assert!(PasswordHasher {
+            bcrypt_cost: 5,
+            ..bcrypt.clone()
+        }
+        .needs_rehash(&bcrypt_hash));
+    }
+}
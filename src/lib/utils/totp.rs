@@ -0,0 +1,83 @@
+//! `totp` module implementing time-based one-time passwords (RFC 6238) used for two-factor authentication.
+use rand::rngs::OsRng;
+use rand::RngCore;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+/// Number of seconds each code is valid for.
+const STEP: u64 = 30;
+
+/// Function that will generate new random secret encoded in base32.
+pub fn generate_secret() -> String {
+    let mut bytes = vec![0u8; 20];
+    OsRng.fill_bytes(&mut bytes);
+    Secret::Raw(bytes).to_encoded().to_string()
+}
+
+/// Function that will create `TOTP` with base32 `secret`, returns `None` if the secret is malformed.
+fn totp(secret: &str, issuer: &str, account: &str) -> Option<TOTP> {
+    let bytes = Secret::Encoded(String::from(secret)).to_bytes().ok()?;
+    Some(TOTP::new_unchecked(
+        Algorithm::SHA1,
+        6,
+        0,
+        STEP,
+        bytes,
+        Some(String::from(issuer)),
+        String::from(account),
+    ))
+}
+
+/// Function that will create `otpauth://` uri used to add the secret to authenticator apps.
+pub fn otpauth_uri(secret: &str, issuer: &str, account: &str) -> Option<String> {
+    totp(secret, issuer, account).map(|totp| totp.get_url())
+}
+
+/// Function that will check the code at `time` (unix seconds), allowing one step of clock skew.
+///
+/// # Returns
+/// The time step of the code, so that it can be rejected if it is used again,
+/// or `None` if the code is invalid.
+pub fn verify(secret: &str, code: &str, time: u64) -> Option<u64> {
+    let totp = totp(secret, "", "")?;
+    let current = time / STEP;
+    [current.saturating_sub(1), current, current + 1]
+        .into_iter()
+        .find(|step| totp.check(code, step * STEP))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Secret `12345678901234567890` of the RFC 6238 test vectors encoded in base32.
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn test_verify() {
+        assert_eq!(verify(RFC_SECRET, "287082", 59), Some(1));
+        assert_eq!(verify(RFC_SECRET, "081804", 1111111109), Some(37037036));
+        assert_eq!(
+            verify(RFC_SECRET, "081804", 1111111109 + STEP),
+            Some(37037036)
+        );
+        assert_eq!(verify(RFC_SECRET, "081804", 1111111109 + 2 * STEP), None);
+        assert_eq!(verify(RFC_SECRET, "000000", 59), None);
+        assert_eq!(verify("not base32!", "287082", 59), None);
+    }
+
+    #[test]
+    fn test_generate_secret() {
+        let secret = generate_secret();
+        assert_eq!(secret.len(), 32);
+        assert_ne!(secret, generate_secret());
+
+        let uri = otpauth_uri(&secret, "Tasks", "email@example.com").unwrap();
+        assert_eq!(
+            uri,
+            format!(
+                "otpauth://totp/Tasks:email%40example.com?secret={}&issuer=Tasks",
+                secret
+            )
+        );
+    }
+}